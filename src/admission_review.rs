@@ -0,0 +1,361 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
+
+use crate::patch;
+use crate::request::KubernetesAdmissionRequest;
+use crate::response::ValidationResponse;
+
+/// `apiVersion` of the AdmissionReview objects
+pub const ADMISSION_REVIEW_API_VERSION: &str = "admission.k8s.io/v1";
+/// `kind` of the AdmissionReview objects
+pub const ADMISSION_REVIEW_KIND: &str = "AdmissionReview";
+/// The only patch type supported by Kubernetes
+pub const JSON_PATCH_TYPE: &str = "JSONPatch";
+
+/// The `admission.k8s.io/v1` AdmissionReview envelope, exchanged between the
+/// Kubernetes API server and the admission webhooks.
+///
+/// The API server sends the request, the webhook answers with the same
+/// envelope holding the response.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AdmissionReview {
+    pub api_version: String,
+    pub kind: String,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_request"
+    )]
+    pub request: Option<KubernetesAdmissionRequest>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<AdmissionResponse>,
+}
+
+/// The `response` of an AdmissionReview, as seen by the API server
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AdmissionResponse {
+    /// UID of the request being answered
+    pub uid: String,
+    /// True if the request has been accepted, false otherwise
+    pub allowed: bool,
+    /// Reason of the rejection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<AdmissionResponseStatus>,
+    /// Base64 encoded JSON Patch (RFC 6902) to apply to the object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch: Option<String>,
+    /// Type of the patch, always `JSONPatch`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_annotations: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<Vec<String>>,
+}
+
+/// The subset of `meta.k8s.io/v1.Status` used by admission responses
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AdmissionResponseStatus {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl AdmissionReview {
+    /// Parse an AdmissionReview, e.g. one captured from the logs of the API
+    /// server
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        let review: AdmissionReview = serde_json::from_slice(data)
+            .map_err(|e| anyhow!("cannot parse AdmissionReview: {}", e))?;
+        if review.api_version != ADMISSION_REVIEW_API_VERSION
+            || review.kind != ADMISSION_REVIEW_KIND
+        {
+            return Err(anyhow!(
+                "expected {}/{}, got {}/{}",
+                ADMISSION_REVIEW_API_VERSION,
+                ADMISSION_REVIEW_KIND,
+                review.api_version,
+                review.kind
+            ));
+        }
+        Ok(review)
+    }
+
+    /// Create the AdmissionReview sent by the API server
+    pub fn new_request(request: KubernetesAdmissionRequest) -> Self {
+        AdmissionReview {
+            api_version: ADMISSION_REVIEW_API_VERSION.to_string(),
+            kind: ADMISSION_REVIEW_KIND.to_string(),
+            request: Some(request),
+            response: None,
+        }
+    }
+
+    /// Create the AdmissionReview answering `request` with the outcome of a
+    /// policy evaluation. The mutated object is turned into a JSON Patch.
+    pub fn new_response(
+        request: &KubernetesAdmissionRequest,
+        response: &ValidationResponse,
+    ) -> Result<Self> {
+        Ok(AdmissionReview {
+            api_version: ADMISSION_REVIEW_API_VERSION.to_string(),
+            kind: ADMISSION_REVIEW_KIND.to_string(),
+            request: None,
+            response: Some(AdmissionResponse::from_validation_response(
+                request, response,
+            )?),
+        })
+    }
+
+    /// The payload given to the `validate` function of a policy to evaluate
+    /// the request of this AdmissionReview
+    pub fn validation_payload<T: Serialize>(&self, settings: &T) -> Result<Vec<u8>> {
+        let request = self
+            .request
+            .as_ref()
+            .ok_or_else(|| anyhow!("the AdmissionReview has no request"))?;
+        Ok(serde_json::to_vec(&serde_json::json!({
+            "settings": settings,
+            "request": request,
+        }))?)
+    }
+}
+
+impl AdmissionResponse {
+    /// Convert the outcome of a policy evaluation into the response the API
+    /// server would receive
+    /// # Arguments
+    /// * `request` - the request evaluated by the policy
+    /// * `response` - the response of the policy
+    pub fn from_validation_response(
+        request: &KubernetesAdmissionRequest,
+        response: &ValidationResponse,
+    ) -> Result<Self> {
+        let status = (response.code.is_some() || response.message.is_some()).then(|| {
+            AdmissionResponseStatus {
+                code: response.code,
+                message: response.message.clone(),
+            }
+        });
+
+        let patch = match &response.mutated_object {
+            Some(mutated_object) => {
+                let patch = patch::diff(&request.object, mutated_object);
+                Some(BASE64.encode(serde_json::to_vec(&patch)?))
+            }
+            None => None,
+        };
+
+        Ok(AdmissionResponse {
+            uid: request.uid.clone(),
+            allowed: response.accepted,
+            status,
+            patch_type: patch.as_ref().map(|_| JSON_PATCH_TYPE.to_string()),
+            patch,
+            audit_annotations: response.audit_annotations.clone(),
+            warnings: response.warnings.clone(),
+        })
+    }
+
+    /// Decode the JSON Patch of the response, if any
+    pub fn json_patch(&self) -> Result<Option<patch::Patch>> {
+        let Some(encoded) = &self.patch else {
+            return Ok(None);
+        };
+        if let Some(patch_type) = &self.patch_type {
+            if patch_type != JSON_PATCH_TYPE {
+                return Err(anyhow!("unsupported patch type {}", patch_type));
+            }
+        }
+        let decoded = BASE64
+            .decode(encoded)
+            .map_err(|e| anyhow!("cannot decode patch: {}", e))?;
+        let patch = serde_json::from_slice(&decoded)
+            .map_err(|e| anyhow!("cannot parse JSON patch: {}", e))?;
+        Ok(Some(patch))
+    }
+
+    /// The object that would be persisted by the API server: `object` with
+    /// the patch of the response applied
+    pub fn patched_object(&self, object: &serde_json::Value) -> Result<serde_json::Value> {
+        match self.json_patch()? {
+            Some(patch) => patch::apply(object, &patch),
+            None => Ok(object.clone()),
+        }
+    }
+}
+
+/// Names used by the API server for the fields of the request. Serialization
+/// of `KubernetesAdmissionRequest` uses snake case, while deserialization
+/// accepts both.
+const REQUEST_FIELDS_CAMEL_CASE: [(&str, &str); 7] = [
+    ("sub_resource", "subResource"),
+    ("request_kind", "requestKind"),
+    ("request_resource", "requestResource"),
+    ("request_sub_resource", "requestSubResource"),
+    ("user_info", "userInfo"),
+    ("old_object", "oldObject"),
+    ("dry_run", "dryRun"),
+];
+
+fn serialize_request<S>(
+    request: &Option<KubernetesAdmissionRequest>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let Some(request) = request else {
+        return serializer.serialize_none();
+    };
+    let mut value = serde_json::to_value(request).map_err(serde::ser::Error::custom)?;
+    if let Some(fields) = value.as_object_mut() {
+        for (snake_case, camel_case) in REQUEST_FIELDS_CAMEL_CASE {
+            if let Some(field) = fields.remove(snake_case) {
+                fields.insert(camel_case.to_string(), field);
+            }
+        }
+    }
+    value.serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::Operation;
+    use serde_json::json;
+
+    const CAPTURED_REVIEW: &str = r#"{
+        "apiVersion": "admission.k8s.io/v1",
+        "kind": "AdmissionReview",
+        "request": {
+            "uid": "705ab4f5-6393-11e8-b7cc-42010a800002",
+            "kind": {"group": "", "version": "v1", "kind": "Pod"},
+            "resource": {"group": "", "version": "v1", "resource": "pods"},
+            "requestKind": {"group": "", "version": "v1", "kind": "Pod"},
+            "requestResource": {"group": "", "version": "v1", "resource": "pods"},
+            "name": "nginx",
+            "namespace": "default",
+            "operation": "CREATE",
+            "userInfo": {"username": "admin", "groups": ["system:authenticated"]},
+            "object": {
+                "apiVersion": "v1",
+                "kind": "Pod",
+                "metadata": {"name": "nginx", "labels": {"app": "nginx"}}
+            },
+            "oldObject": null,
+            "dryRun": true,
+            "options": {"apiVersion": "meta.k8s.io/v1", "kind": "CreateOptions"}
+        }
+    }"#;
+
+    fn mutated_response() -> ValidationResponse {
+        ValidationResponse {
+            accepted: true,
+            message: None,
+            code: None,
+            mutated_object: Some(json!({
+                "apiVersion": "v1",
+                "kind": "Pod",
+                "metadata": {"name": "nginx", "labels": {"app": "nginx", "owner": "team-a"}}
+            })),
+            audit_annotations: None,
+            warnings: Some(vec!["owner label added".to_string()]),
+        }
+    }
+
+    #[test]
+    fn parse_captured_review() {
+        let review = AdmissionReview::from_slice(CAPTURED_REVIEW.as_bytes()).unwrap();
+        let request = review.request.unwrap();
+
+        assert_eq!(request.uid, "705ab4f5-6393-11e8-b7cc-42010a800002");
+        assert_eq!(request.operation, Operation::Create);
+        assert_eq!(request.request_kind.kind, "Pod");
+        assert!(request.dry_run);
+        assert!(review.response.is_none());
+    }
+
+    #[test]
+    fn parse_review_with_wrong_kind() {
+        let err = AdmissionReview::from_slice(
+            br#"{"apiVersion": "admission.k8s.io/v1beta1", "kind": "AdmissionReview"}"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected admission.k8s.io/v1/AdmissionReview, got admission.k8s.io/v1beta1/AdmissionReview"
+        );
+    }
+
+    #[test]
+    fn request_is_serialized_like_the_api_server_does() {
+        let review = AdmissionReview::from_slice(CAPTURED_REVIEW.as_bytes()).unwrap();
+        let serialized = serde_json::to_value(&review).unwrap();
+
+        assert_eq!(serialized["request"]["requestKind"]["kind"], "Pod");
+        assert_eq!(serialized["request"]["userInfo"]["username"], "admin");
+        assert_eq!(serialized["request"]["dryRun"], true);
+        assert!(serialized["request"].get("request_kind").is_none());
+        assert!(serialized.get("response").is_none());
+    }
+
+    #[test]
+    fn mutation_becomes_a_json_patch() {
+        let review = AdmissionReview::from_slice(CAPTURED_REVIEW.as_bytes()).unwrap();
+        let request = review.request.unwrap();
+
+        let response_review = AdmissionReview::new_response(&request, &mutated_response()).unwrap();
+        let response = response_review.response.unwrap();
+        assert_eq!(response.uid, request.uid);
+        assert!(response.allowed);
+        assert!(response.status.is_none());
+        assert_eq!(response.patch_type, Some(JSON_PATCH_TYPE.to_string()));
+        assert_eq!(
+            serde_json::to_value(response.json_patch().unwrap().unwrap()).unwrap(),
+            json!([{"op": "add", "path": "/metadata/labels/owner", "value": "team-a"}])
+        );
+        assert_eq!(
+            response.patched_object(&request.object).unwrap(),
+            mutated_response().mutated_object.unwrap()
+        );
+        assert_eq!(
+            response.warnings,
+            Some(vec!["owner label added".to_string()])
+        );
+    }
+
+    #[test]
+    fn rejection_sets_status() {
+        let request = KubernetesAdmissionRequest {
+            uid: "uid".to_string(),
+            ..Default::default()
+        };
+        let response = AdmissionResponse::from_validation_response(
+            &request,
+            &ValidationResponse {
+                accepted: false,
+                message: Some("privileged containers are not allowed".to_string()),
+                code: Some(403),
+                mutated_object: None,
+                audit_annotations: None,
+                warnings: None,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({
+                "uid": "uid",
+                "allowed": false,
+                "status": {"code": 403, "message": "privileged containers are not allowed"}
+            })
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use k8s_openapi::api::core::v1::{
+    Container, EnvVar, EphemeralContainer, PodSpec, ResourceRequirements, SecurityContext,
+    VolumeMount,
+};
+use std::fmt;
+
+/// The list of a PodSpec holding a container
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContainerKind {
+    Container,
+    InitContainer,
+    EphemeralContainer,
+}
+
+impl ContainerKind {
+    /// Name of the PodSpec field holding this kind of containers
+    pub fn field_name(&self) -> &'static str {
+        match self {
+            ContainerKind::Container => "containers",
+            ContainerKind::InitContainer => "initContainers",
+            ContainerKind::EphemeralContainer => "ephemeralContainers",
+        }
+    }
+}
+
+impl fmt::Display for ContainerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.field_name())
+    }
+}
+
+/// Reference to a container of a PodSpec. Regular and init containers are
+/// described by `Container`, while ephemeral containers have their own type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContainerRef<'a> {
+    Container(&'a Container),
+    EphemeralContainer(&'a EphemeralContainer),
+}
+
+/// Mutable reference to a container of a PodSpec
+#[derive(Debug, PartialEq)]
+pub enum ContainerRefMut<'a> {
+    Container(&'a mut Container),
+    EphemeralContainer(&'a mut EphemeralContainer),
+}
+
+// Generate accessors for the fields shared by `Container` and `EphemeralContainer`
+macro_rules! container_field {
+    ($f:ident, $t:ty) => {
+        pub fn $f(&self) -> $t {
+            match self {
+                Self::Container(c) => &c.$f,
+                Self::EphemeralContainer(c) => &c.$f,
+            }
+        }
+    };
+}
+
+macro_rules! container_field_mut {
+    ($f:ident, $field:ident, $t:ty) => {
+        pub fn $f(&mut self) -> $t {
+            match self {
+                Self::Container(c) => &mut c.$field,
+                Self::EphemeralContainer(c) => &mut c.$field,
+            }
+        }
+    };
+}
+
+impl ContainerRef<'_> {
+    container_field!(name, &String);
+    container_field!(image, &Option<String>);
+    container_field!(image_pull_policy, &Option<String>);
+    container_field!(env, &Option<Vec<EnvVar>>);
+    container_field!(resources, &Option<ResourceRequirements>);
+    container_field!(security_context, &Option<SecurityContext>);
+    container_field!(volume_mounts, &Option<Vec<VolumeMount>>);
+}
+
+impl ContainerRefMut<'_> {
+    container_field!(name, &String);
+    container_field!(image, &Option<String>);
+    container_field!(image_pull_policy, &Option<String>);
+    container_field!(env, &Option<Vec<EnvVar>>);
+    container_field!(resources, &Option<ResourceRequirements>);
+    container_field!(security_context, &Option<SecurityContext>);
+    container_field!(volume_mounts, &Option<Vec<VolumeMount>>);
+
+    container_field_mut!(image_mut, image, &mut Option<String>);
+    container_field_mut!(
+        image_pull_policy_mut,
+        image_pull_policy,
+        &mut Option<String>
+    );
+    container_field_mut!(env_mut, env, &mut Option<Vec<EnvVar>>);
+    container_field_mut!(resources_mut, resources, &mut Option<ResourceRequirements>);
+    container_field_mut!(
+        security_context_mut,
+        security_context,
+        &mut Option<SecurityContext>
+    );
+    container_field_mut!(
+        volume_mounts_mut,
+        volume_mounts,
+        &mut Option<Vec<VolumeMount>>
+    );
+}
+
+/// A container found inside of a PodSpec
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerEntry<'a> {
+    /// The list holding the container
+    pub kind: ContainerKind,
+    /// Position of the container inside of its list
+    pub index: usize,
+    /// JSON path of the container, e.g. `spec.template.spec.initContainers[2]`
+    pub path: String,
+    pub container: ContainerRef<'a>,
+}
+
+/// A container found inside of a PodSpec, that can be changed
+#[derive(Debug, PartialEq)]
+pub struct ContainerEntryMut<'a> {
+    /// The list holding the container
+    pub kind: ContainerKind,
+    /// Position of the container inside of its list
+    pub index: usize,
+    /// JSON path of the container, e.g. `spec.template.spec.initContainers[2]`
+    pub path: String,
+    pub container: ContainerRefMut<'a>,
+}
+
+fn container_path(pod_spec_path: &str, kind: ContainerKind, index: usize) -> String {
+    if pod_spec_path.is_empty() {
+        format!("{}[{}]", kind, index)
+    } else {
+        format!("{}.{}[{}]", pod_spec_path, kind, index)
+    }
+}
+
+/// Iterate over all the containers of the PodSpec: regular containers first,
+/// then init containers and finally ephemeral containers.
+/// # Arguments
+/// * `pod_spec` - the PodSpec holding the containers
+/// * `pod_spec_path` - JSON path of the PodSpec, used to build the path of the
+///   containers (e.g. `spec.template.spec`, see [`crate::pod_template::pod_spec_path`])
+pub fn containers<'a>(
+    pod_spec: &'a PodSpec,
+    pod_spec_path: &str,
+) -> impl Iterator<Item = ContainerEntry<'a>> {
+    let pod_spec_path = pod_spec_path.to_string();
+    let regular = pod_spec
+        .containers
+        .iter()
+        .map(|c| (ContainerKind::Container, ContainerRef::Container(c)));
+    let init = pod_spec
+        .init_containers
+        .iter()
+        .flatten()
+        .map(|c| (ContainerKind::InitContainer, ContainerRef::Container(c)));
+    let ephemeral = pod_spec.ephemeral_containers.iter().flatten().map(|c| {
+        (
+            ContainerKind::EphemeralContainer,
+            ContainerRef::EphemeralContainer(c),
+        )
+    });
+
+    with_positions(regular.chain(init).chain(ephemeral)).map(move |(kind, index, container)| {
+        ContainerEntry {
+            kind,
+            index,
+            path: container_path(&pod_spec_path, kind, index),
+            container,
+        }
+    })
+}
+
+/// Like [`containers`], but allows the containers to be changed. The mutated
+/// PodSpec can then be given to [`crate::mutate_pod_spec_from_request`].
+pub fn containers_mut<'a>(
+    pod_spec: &'a mut PodSpec,
+    pod_spec_path: &str,
+) -> impl Iterator<Item = ContainerEntryMut<'a>> {
+    let pod_spec_path = pod_spec_path.to_string();
+    let regular = pod_spec
+        .containers
+        .iter_mut()
+        .map(|c| (ContainerKind::Container, ContainerRefMut::Container(c)));
+    let init = pod_spec
+        .init_containers
+        .iter_mut()
+        .flatten()
+        .map(|c| (ContainerKind::InitContainer, ContainerRefMut::Container(c)));
+    let ephemeral = pod_spec.ephemeral_containers.iter_mut().flatten().map(|c| {
+        (
+            ContainerKind::EphemeralContainer,
+            ContainerRefMut::EphemeralContainer(c),
+        )
+    });
+
+    with_positions(regular.chain(init).chain(ephemeral)).map(move |(kind, index, container)| {
+        ContainerEntryMut {
+            kind,
+            index,
+            path: container_path(&pod_spec_path, kind, index),
+            container,
+        }
+    })
+}
+
+/// Add to each container its position inside of the list it belongs to
+fn with_positions<T>(
+    iter: impl Iterator<Item = (ContainerKind, T)>,
+) -> impl Iterator<Item = (ContainerKind, usize, T)> {
+    let mut last_kind = None;
+    let mut index = 0;
+    iter.map(move |(kind, container)| {
+        if last_kind == Some(kind) {
+            index += 1;
+        } else {
+            last_kind = Some(kind);
+            index = 0;
+        }
+        (kind, index, container)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pod_spec() -> PodSpec {
+        PodSpec {
+            containers: vec![
+                Container {
+                    name: "nginx".to_string(),
+                    image: Some("nginx:latest".to_string()),
+                    ..Default::default()
+                },
+                Container {
+                    name: "sidecar".to_string(),
+                    image: Some("busybox:latest".to_string()),
+                    ..Default::default()
+                },
+            ],
+            init_containers: Some(vec![Container {
+                name: "init".to_string(),
+                ..Default::default()
+            }]),
+            ephemeral_containers: Some(vec![EphemeralContainer {
+                name: "debug".to_string(),
+                image: Some("busybox:latest".to_string()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn iterate_over_all_containers() {
+        let pod_spec = pod_spec();
+        let entries: Vec<(ContainerKind, usize, String, String)> =
+            containers(&pod_spec, "spec.template.spec")
+                .map(|e| (e.kind, e.index, e.path, e.container.name().clone()))
+                .collect();
+
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    ContainerKind::Container,
+                    0,
+                    "spec.template.spec.containers[0]".to_string(),
+                    "nginx".to_string()
+                ),
+                (
+                    ContainerKind::Container,
+                    1,
+                    "spec.template.spec.containers[1]".to_string(),
+                    "sidecar".to_string()
+                ),
+                (
+                    ContainerKind::InitContainer,
+                    0,
+                    "spec.template.spec.initContainers[0]".to_string(),
+                    "init".to_string()
+                ),
+                (
+                    ContainerKind::EphemeralContainer,
+                    0,
+                    "spec.template.spec.ephemeralContainers[0]".to_string(),
+                    "debug".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn iterate_over_empty_pod_spec() {
+        let pod_spec = PodSpec::default();
+        assert_eq!(containers(&pod_spec, "spec").count(), 0);
+    }
+
+    #[test]
+    fn mutate_all_containers() {
+        let mut pod_spec = pod_spec();
+        for mut entry in containers_mut(&mut pod_spec, "") {
+            if entry.container.image().is_none() {
+                *entry.container.image_mut() = Some(format!("registry.local/{}", entry.path));
+            }
+            *entry.container.image_pull_policy_mut() = Some("Always".to_string());
+        }
+
+        assert_eq!(
+            pod_spec.init_containers.as_ref().unwrap()[0].image,
+            Some("registry.local/initContainers[0]".to_string())
+        );
+        assert!(containers(&pod_spec, "")
+            .all(|e| e.container.image_pull_policy() == &Some("Always".to_string())));
+    }
+}
@@ -3,8 +3,10 @@ pub mod admission_policy_group;
 pub mod cluster_admission_policy;
 pub mod cluster_admission_policy_group;
 pub mod common;
+pub mod matching;
 
 pub use admission_policy::AdmissionPolicy;
 pub use admission_policy_group::AdmissionPolicyGroup;
 pub use cluster_admission_policy::ClusterAdmissionPolicy;
 pub use cluster_admission_policy_group::ClusterAdmissionPolicyGroup;
+pub use matching::{policy_matches, MatchCriteria};
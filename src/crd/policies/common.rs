@@ -2,14 +2,7 @@
 /// policy types.
 use k8s_openapi::apimachinery::pkg::runtime::RawExtension;
 
-#[derive(
-    Clone, Default, Debug, serde::Deserialize, serde::Serialize, PartialEq, schemars::JsonSchema,
-)]
-pub enum PolicyMode {
-    #[default]
-    Protect,
-    Monitor,
-}
+pub use crate::request::PolicyMode;
 
 #[derive(
     Clone, Default, Debug, serde::Deserialize, serde::Serialize, PartialEq, schemars::JsonSchema,
@@ -0,0 +1,507 @@
+//! Local evaluation of the `rules`, `namespaceSelector`, `objectSelector` and
+//! `matchPolicy` fields of the policy specs. This tells whether the API server
+//! would send an admission request to the policy.
+//!
+//! The `matchConditions` are CEL expressions and are not evaluated.
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use k8s_openapi::{
+    api::admissionregistration::v1::RuleWithOperations,
+    apimachinery::pkg::apis::meta::v1::LabelSelector,
+};
+
+use crate::crd::policies::{
+    admission_policy::AdmissionPolicySpec, admission_policy_group::AdmissionPolicyGroupSpec,
+    cluster_admission_policy::ClusterAdmissionPolicySpec,
+    cluster_admission_policy_group::ClusterAdmissionPolicyGroupSpec, common::MatchPolicy,
+};
+use crate::label_selector::Selector;
+use crate::request::KubernetesAdmissionRequest;
+
+/// The fields of a policy spec that decide which admission requests are
+/// sent to the policy
+pub trait MatchCriteria {
+    /// The operations and the resources the policy cares about
+    fn rules(&self) -> &[RuleWithOperations];
+
+    /// How the rules are matched against the incoming requests
+    fn match_policy(&self) -> MatchPolicy;
+
+    /// Selector evaluated against the labels of the object and of the old object
+    fn object_selector(&self) -> Option<&LabelSelector>;
+
+    /// Selector evaluated against the labels of the namespace of the object.
+    /// Namespaced policies do not have one.
+    fn namespace_selector(&self) -> Option<&LabelSelector> {
+        None
+    }
+}
+
+macro_rules! impl_match_criteria {
+    ($spec:ty $(, $namespace_selector:ident)?) => {
+        impl MatchCriteria for $spec {
+            fn rules(&self) -> &[RuleWithOperations] {
+                self.rules.as_deref().unwrap_or_default()
+            }
+
+            fn match_policy(&self) -> MatchPolicy {
+                self.match_policy.clone().unwrap_or_default()
+            }
+
+            fn object_selector(&self) -> Option<&LabelSelector> {
+                self.object_selector.as_ref()
+            }
+
+            $(
+                fn namespace_selector(&self) -> Option<&LabelSelector> {
+                    self.$namespace_selector.as_ref()
+                }
+            )?
+        }
+    };
+}
+
+impl_match_criteria!(ClusterAdmissionPolicySpec, namespace_selector);
+impl_match_criteria!(ClusterAdmissionPolicyGroupSpec, namespace_selector);
+impl_match_criteria!(AdmissionPolicySpec);
+impl_match_criteria!(AdmissionPolicyGroupSpec);
+
+/// Tells whether the API server would send `request` to a policy, or a policy
+/// group, defined by `spec`. The request must match one of the rules, the
+/// namespace selector and the object selector.
+///
+/// `AdmissionPolicy` and `AdmissionPolicyGroup` resources also evaluate only
+/// the requests made inside of their own namespace, checking that is up to
+/// the caller.
+/// # Arguments
+/// * `spec` - the spec of the policy
+/// * `request` - the admission request to be evaluated
+/// * `namespace_labels` - the labels of the namespace of the object. They are
+///   required only when the namespace selector is not empty, and the object is
+///   neither cluster wide nor a Namespace.
+///
+/// # Matching policy
+///
+/// With `matchPolicy: Exact` the rules are matched against the resource of the
+/// original API request (`requestResource` and `requestSubResource`).
+/// With `matchPolicy: Equivalent` the API server matches also the versions
+/// and groups through which the same objects can be changed. The SDK does not
+/// know which resources are served by the cluster, hence all the versions of
+/// a resource are considered equivalent, and both `resource` and
+/// `requestResource` are matched.
+pub fn policy_matches<S: MatchCriteria + ?Sized>(
+    spec: &S,
+    request: &KubernetesAdmissionRequest,
+    namespace_labels: Option<&BTreeMap<String, String>>,
+) -> Result<bool> {
+    if !rules_match(spec.rules(), &spec.match_policy(), request) {
+        return Ok(false);
+    }
+    if let Some(selector) = spec.namespace_selector() {
+        if !namespace_selector_matches(selector, request, namespace_labels)? {
+            return Ok(false);
+        }
+    }
+    match spec.object_selector() {
+        Some(selector) => object_selector_matches(selector, request),
+        None => Ok(true),
+    }
+}
+
+/// The resource, as seen by the rules
+struct ResourceAttributes<'a> {
+    group: &'a str,
+    version: &'a str,
+    resource: &'a str,
+    sub_resource: &'a str,
+}
+
+impl<'a> ResourceAttributes<'a> {
+    /// The resource of the original API request, falling back to the
+    /// converted one when the request does not report it
+    fn requested(request: &'a KubernetesAdmissionRequest) -> Self {
+        if request.request_resource.resource.is_empty() {
+            return Self::converted(request);
+        }
+        ResourceAttributes {
+            group: &request.request_resource.group,
+            version: &request.request_resource.version,
+            resource: &request.request_resource.resource,
+            sub_resource: &request.request_sub_resource,
+        }
+    }
+
+    /// The resource the request has been converted to
+    fn converted(request: &'a KubernetesAdmissionRequest) -> Self {
+        ResourceAttributes {
+            group: &request.resource.group,
+            version: &request.resource.version,
+            resource: &request.resource.resource,
+            sub_resource: &request.sub_resource,
+        }
+    }
+}
+
+fn rules_match(
+    rules: &[RuleWithOperations],
+    match_policy: &MatchPolicy,
+    request: &KubernetesAdmissionRequest,
+) -> bool {
+    let candidates = match match_policy {
+        MatchPolicy::Exact => vec![(ResourceAttributes::requested(request), true)],
+        MatchPolicy::Equivalent => vec![
+            (ResourceAttributes::requested(request), false),
+            (ResourceAttributes::converted(request), false),
+        ],
+    };
+
+    rules.iter().any(|rule| {
+        operation_matches(rule, request)
+            && scope_matches(rule, request)
+            && candidates.iter().any(|(attributes, match_version)| {
+                resource_matches(rule, attributes, *match_version)
+            })
+    })
+}
+
+/// Tells whether `values` holds either `value` or the `*` wildcard
+fn contains_or_wildcard(values: &Option<Vec<String>>, value: &str) -> bool {
+    values.iter().flatten().any(|v| v == "*" || v == value)
+}
+
+fn operation_matches(rule: &RuleWithOperations, request: &KubernetesAdmissionRequest) -> bool {
+    contains_or_wildcard(&rule.operations, request.operation.as_str())
+}
+
+fn resource_matches(
+    rule: &RuleWithOperations,
+    attributes: &ResourceAttributes,
+    match_version: bool,
+) -> bool {
+    if !contains_or_wildcard(&rule.api_groups, attributes.group) {
+        return false;
+    }
+    if match_version && !contains_or_wildcard(&rule.api_versions, attributes.version) {
+        return false;
+    }
+
+    // `pods` matches only the pods, `pods/*` the pods and all their
+    // subresources, `*/scale` the scale subresource of all the resources and
+    // `*/*` everything
+    rule.resources.iter().flatten().any(|rule_resource| {
+        let (resource, sub_resource) = rule_resource
+            .split_once('/')
+            .unwrap_or((rule_resource.as_str(), ""));
+        (resource == "*" || resource == attributes.resource)
+            && (sub_resource == "*" || sub_resource == attributes.sub_resource)
+    })
+}
+
+fn is_namespace_request(request: &KubernetesAdmissionRequest) -> bool {
+    request.resource.group.is_empty() && request.resource.resource == "namespaces"
+}
+
+fn scope_matches(rule: &RuleWithOperations, request: &KubernetesAdmissionRequest) -> bool {
+    // Namespace objects are cluster wide, but their requests carry the
+    // name of the namespace
+    match rule.scope.as_deref().unwrap_or("*") {
+        "Cluster" => is_namespace_request(request) || request.namespace.is_empty(),
+        "Namespaced" => !is_namespace_request(request) && !request.namespace.is_empty(),
+        _ => true,
+    }
+}
+
+fn namespace_selector_matches(
+    selector: &LabelSelector,
+    request: &KubernetesAdmissionRequest,
+    namespace_labels: Option<&BTreeMap<String, String>>,
+) -> Result<bool> {
+    let is_namespace = is_namespace_request(request);
+    // cluster wide resources, other than namespaces, are never skipped
+    if request.namespace.is_empty() && !is_namespace {
+        return Ok(true);
+    }
+    let selector =
+        Selector::try_from(selector).map_err(|e| anyhow!("invalid namespace selector: {}", e))?;
+    if selector.is_empty() {
+        return Ok(true);
+    }
+
+    let labels = if is_namespace {
+        object_labels(&request.object)
+            .or_else(|| object_labels(&request.old_object))
+            .unwrap_or_default()
+    } else {
+        namespace_labels.cloned().ok_or_else(|| {
+            anyhow!(
+                "the labels of namespace '{}' are required to evaluate the namespace selector",
+                request.namespace
+            )
+        })?
+    };
+    Ok(selector.matches(&labels))
+}
+
+fn object_selector_matches(
+    selector: &LabelSelector,
+    request: &KubernetesAdmissionRequest,
+) -> Result<bool> {
+    let selector =
+        Selector::try_from(selector).map_err(|e| anyhow!("invalid object selector: {}", e))?;
+    if selector.is_empty() {
+        return Ok(true);
+    }
+    // a missing object, like the old object of a CREATE, never matches
+    Ok([&request.object, &request.old_object]
+        .into_iter()
+        .filter_map(object_labels)
+        .any(|labels| selector.matches(&labels)))
+}
+
+/// The labels of `object`, `None` when there's no object
+fn object_labels(object: &serde_json::Value) -> Option<BTreeMap<String, String>> {
+    if !object.is_object() {
+        return None;
+    }
+    let labels = object
+        .pointer("/metadata/labels")
+        .and_then(|labels| labels.as_object())
+        .map(|labels| {
+            labels
+                .iter()
+                .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn spec(yaml: &str) -> ClusterAdmissionPolicySpec {
+        serde_yaml::from_str(yaml).expect("cannot deserialize ClusterAdmissionPolicySpec")
+    }
+
+    fn request(value: serde_json::Value) -> KubernetesAdmissionRequest {
+        serde_json::from_value(value).expect("cannot deserialize KubernetesAdmissionRequest")
+    }
+
+    fn pod_request(operation: &str, sub_resource: &str) -> KubernetesAdmissionRequest {
+        request(json!({
+            "kind": {"group": "", "version": "v1", "kind": "Pod"},
+            "resource": {"group": "", "version": "v1", "resource": "pods"},
+            "subResource": sub_resource,
+            "requestKind": {"group": "", "version": "v1", "kind": "Pod"},
+            "requestResource": {"group": "", "version": "v1", "resource": "pods"},
+            "requestSubResource": sub_resource,
+            "namespace": "default",
+            "operation": operation,
+            "object": {"metadata": {"name": "nginx", "labels": {"app": "nginx"}}},
+        }))
+    }
+
+    const PODS_SPEC: &str = r#"
+module: registry://ghcr.io/kubewarden/policies/pod-privileged:v0.1.9
+rules:
+  - apiGroups: [""]
+    apiVersions: ["v1"]
+    resources: ["pods"]
+    operations: ["CREATE", "UPDATE"]
+"#;
+
+    #[test]
+    fn rules_operations_and_resources() {
+        let spec = spec(PODS_SPEC);
+
+        assert!(policy_matches(&spec, &pod_request("CREATE", ""), None).unwrap());
+        assert!(!policy_matches(&spec, &pod_request("DELETE", ""), None).unwrap());
+        assert!(!policy_matches(&spec, &pod_request("UPDATE", "status"), None).unwrap());
+        assert!(!policy_matches(
+            &ClusterAdmissionPolicySpec::default(),
+            &pod_request("CREATE", ""),
+            None
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn rules_wildcards() {
+        for (resources, sub_resource, expected) in [
+            (vec!["*"], "", true),
+            (vec!["*"], "status", false),
+            (vec!["pods/*"], "status", true),
+            (vec!["pods/*"], "", true),
+            (vec!["*/status"], "status", true),
+            (vec!["*/status"], "exec", false),
+            (vec!["*/*"], "exec", true),
+            (vec!["deployments", "pods/exec"], "exec", true),
+        ] {
+            let spec = ClusterAdmissionPolicySpec {
+                rules: Some(vec![RuleWithOperations {
+                    api_groups: Some(vec!["*".to_string()]),
+                    api_versions: Some(vec!["*".to_string()]),
+                    operations: Some(vec!["*".to_string()]),
+                    resources: Some(resources.iter().map(|r| r.to_string()).collect()),
+                    scope: None,
+                }]),
+                ..Default::default()
+            };
+            assert_eq!(
+                policy_matches(&spec, &pod_request("CONNECT", sub_resource), None).unwrap(),
+                expected,
+                "{:?} {}",
+                resources,
+                sub_resource
+            );
+        }
+    }
+
+    #[test]
+    fn rules_scope() {
+        let rule = |scope: &str| ClusterAdmissionPolicySpec {
+            rules: Some(vec![RuleWithOperations {
+                api_groups: Some(vec!["*".to_string()]),
+                api_versions: Some(vec!["*".to_string()]),
+                operations: Some(vec!["*".to_string()]),
+                resources: Some(vec!["*".to_string()]),
+                scope: Some(scope.to_string()),
+            }]),
+            ..Default::default()
+        };
+        let namespace = request(json!({
+            "resource": {"group": "", "version": "v1", "resource": "namespaces"},
+            "namespace": "team-a",
+            "operation": "CREATE",
+        }));
+
+        assert!(policy_matches(&rule("Namespaced"), &pod_request("CREATE", ""), None).unwrap());
+        assert!(!policy_matches(&rule("Cluster"), &pod_request("CREATE", ""), None).unwrap());
+        assert!(policy_matches(&rule("Cluster"), &namespace, None).unwrap());
+        assert!(!policy_matches(&rule("Namespaced"), &namespace, None).unwrap());
+        assert!(policy_matches(&rule("*"), &namespace, None).unwrap());
+    }
+
+    #[test]
+    fn match_policy() {
+        let deployment = request(json!({
+            "resource": {"group": "apps", "version": "v1", "resource": "deployments"},
+            "requestResource": {"group": "apps", "version": "v1beta1", "resource": "deployments"},
+            "namespace": "default",
+            "operation": "CREATE",
+        }));
+        let mut spec = spec(
+            r#"
+module: registry://ghcr.io/kubewarden/policies/replicas:v0.1.0
+rules:
+  - apiGroups: ["apps"]
+    apiVersions: ["v1beta2"]
+    resources: ["deployments"]
+    operations: ["CREATE"]
+"#,
+        );
+
+        assert!(policy_matches(&spec, &deployment, None).unwrap());
+
+        spec.match_policy = Some(MatchPolicy::Exact);
+        assert!(!policy_matches(&spec, &deployment, None).unwrap());
+        spec.rules.as_mut().unwrap()[0].api_versions = Some(vec!["v1beta1".to_string()]);
+        assert!(policy_matches(&spec, &deployment, None).unwrap());
+    }
+
+    #[test]
+    fn object_selector() {
+        let mut spec = spec(PODS_SPEC);
+        let mut update = pod_request("UPDATE", "");
+        update.object = json!({"metadata": {"labels": {"app": "nginx", "tier": "frontend"}}});
+        update.old_object = json!({"metadata": {"labels": {"app": "nginx", "tier": "backend"}}});
+
+        for (selector, expected) in [
+            (json!({}), true),
+            (json!({"matchLabels": {"tier": "backend"}}), true),
+            (json!({"matchLabels": {"tier": "database"}}), false),
+            (
+                json!({"matchExpressions": [{"key": "tier", "operator": "NotIn", "values": ["frontend", "backend"]}]}),
+                false,
+            ),
+            (
+                json!({"matchExpressions": [{"key": "team", "operator": "DoesNotExist"}]}),
+                true,
+            ),
+        ] {
+            spec.object_selector = Some(serde_json::from_value(selector.clone()).unwrap());
+            assert_eq!(
+                policy_matches(&spec, &update, None).unwrap(),
+                expected,
+                "{}",
+                selector
+            );
+        }
+
+        // the old object is missing when creating an object
+        spec.object_selector =
+            Some(serde_json::from_value(json!({"matchLabels": {"tier": "backend"}})).unwrap());
+        assert!(!policy_matches(&spec, &pod_request("CREATE", ""), None).unwrap());
+
+        spec.object_selector = Some(
+            serde_json::from_value(
+                json!({"matchExpressions": [{"key": "tier", "operator": "Like"}]}),
+            )
+            .unwrap(),
+        );
+        assert!(policy_matches(&spec, &update, None).is_err());
+    }
+
+    #[test]
+    fn namespace_selector() {
+        let mut spec = spec(PODS_SPEC);
+        spec.namespace_selector = Some(
+            serde_json::from_value(json!({
+                "matchExpressions": [{"key": "environment", "operator": "In", "values": ["prod", "staging"]}]
+            }))
+            .unwrap(),
+        );
+        let prod: BTreeMap<String, String> =
+            [("environment".to_string(), "prod".to_string())].into();
+        let dev: BTreeMap<String, String> = [("environment".to_string(), "dev".to_string())].into();
+        let request = pod_request("CREATE", "");
+
+        assert!(policy_matches(&spec, &request, Some(&prod)).unwrap());
+        assert!(!policy_matches(&spec, &request, Some(&dev)).unwrap());
+        assert!(policy_matches(&spec, &request, None).is_err());
+
+        // namespaced policies have no namespace selector
+        let namespaced: AdmissionPolicySpec = serde_yaml::from_str(PODS_SPEC).unwrap();
+        assert!(policy_matches(&namespaced, &request, Some(&dev)).unwrap());
+    }
+
+    #[test]
+    fn namespace_selector_on_namespaces() {
+        let mut spec = spec(
+            r#"
+module: registry://ghcr.io/kubewarden/policies/namespace-labels:v0.1.0
+rules:
+  - apiGroups: [""]
+    apiVersions: ["v1"]
+    resources: ["namespaces"]
+    operations: ["CREATE"]
+"#,
+        );
+        spec.namespace_selector =
+            Some(serde_json::from_value(json!({"matchLabels": {"environment": "prod"}})).unwrap());
+        let namespace = |labels: serde_json::Value| {
+            request(json!({
+                "resource": {"group": "", "version": "v1", "resource": "namespaces"},
+                "namespace": "team-a",
+                "operation": "CREATE",
+                "object": {"metadata": {"name": "team-a", "labels": labels}},
+            }))
+        };
+
+        assert!(policy_matches(&spec, &namespace(json!({"environment": "prod"})), None).unwrap());
+        assert!(!policy_matches(&spec, &namespace(json!({"environment": "dev"})), None).unwrap());
+    }
+}
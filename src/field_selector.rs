@@ -0,0 +1,373 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::Resource;
+use std::fmt;
+
+/// Fields supported by the API server for all the kinds
+const COMMON_FIELDS: &[&str] = &["metadata.name", "metadata.namespace"];
+
+/// Fields supported by the API server in addition to [`COMMON_FIELDS`], by
+/// group and kind
+const KIND_FIELDS: &[(&str, &str, &[&str])] = &[
+    (
+        "",
+        "Pod",
+        &[
+            "spec.nodeName",
+            "spec.restartPolicy",
+            "spec.schedulerName",
+            "spec.serviceAccountName",
+            "spec.hostNetwork",
+            "status.phase",
+            "status.podIP",
+            "status.podIPs",
+            "status.nominatedNodeName",
+        ],
+    ),
+    (
+        "",
+        "Event",
+        &[
+            "involvedObject.kind",
+            "involvedObject.namespace",
+            "involvedObject.name",
+            "involvedObject.uid",
+            "involvedObject.apiVersion",
+            "involvedObject.resourceVersion",
+            "involvedObject.fieldPath",
+            "reason",
+            "reportingComponent",
+            "source",
+            "type",
+        ],
+    ),
+    ("", "Namespace", &["status.phase"]),
+    ("", "Node", &["spec.unschedulable"]),
+    ("", "ReplicationController", &["status.replicas"]),
+    ("", "Secret", &["type"]),
+    ("apps", "ReplicaSet", &["status.replicas"]),
+    ("batch", "Job", &["status.successful"]),
+    (
+        "certificates.k8s.io",
+        "CertificateSigningRequest",
+        &["spec.signerName"],
+    ),
+];
+
+/// The fields that can be used inside of the field selectors of the given
+/// kind. Kinds that are not known by the SDK, like custom resources, support
+/// only `metadata.name` and `metadata.namespace`. Use
+/// [`FieldSelectorBuilder::field_unchecked`] for the other fields.
+/// # Arguments
+/// * `group` - the API group of the kind, empty for the core group
+/// * `kind` - the kind, e.g. `Pod`
+pub fn supported_fields(group: &str, kind: &str) -> Vec<&'static str> {
+    let kind_fields = KIND_FIELDS
+        .iter()
+        .find(|(g, k, _)| *g == group && *k == kind)
+        .map(|(_, _, fields)| *fields)
+        .unwrap_or_default();
+    COMMON_FIELDS.iter().chain(kind_fields).copied().collect()
+}
+
+/// Operator of a [`FieldRequirement`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FieldOperator {
+    /// `field=value`
+    Equals,
+    /// `field==value`, same as `Equals`
+    DoubleEquals,
+    /// `field!=value`
+    NotEquals,
+}
+
+impl FieldOperator {
+    /// Returns the operator as it's written inside of the selector
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FieldOperator::Equals => "=",
+            FieldOperator::DoubleEquals => "==",
+            FieldOperator::NotEquals => "!=",
+        }
+    }
+}
+
+/// A single condition of a [`FieldSelector`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldRequirement {
+    pub field: String,
+    pub operator: FieldOperator,
+    pub value: String,
+}
+
+impl fmt::Display for FieldRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            self.field,
+            self.operator.as_str(),
+            escape_value(&self.value)
+        )
+    }
+}
+
+/// Escape the characters that have a special meaning inside of the field
+/// selectors: `\`, `,` and `=`
+fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | ',' | '=') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// A field selector: the fields of an object must satisfy all of its
+/// requirements. Once converted to a string, it can be used as the
+/// `field_selector` of the requests made to the kubernetes host capability.
+///
+/// # Example
+///
+/// ```
+/// use k8s_openapi::api::core::v1::Pod;
+/// use kubewarden_policy_sdk::field_selector::FieldSelector;
+/// use kubewarden_policy_sdk::host_capabilities::kubernetes::ListAllResourcesRequest;
+///
+/// let selector = FieldSelector::builder::<Pod>()
+///     .equal("spec.nodeName", "worker-1")
+///     .not_equal("status.phase", "Succeeded")
+///     .build()
+///     .unwrap();
+///
+/// let request = ListAllResourcesRequest {
+///     api_version: "v1".to_string(),
+///     kind: "Pod".to_string(),
+///     field_selector: Some(selector.to_string()),
+///     ..Default::default()
+/// };
+/// assert_eq!(
+///     request.field_selector.unwrap(),
+///     "spec.nodeName=worker-1,status.phase!=Succeeded"
+/// );
+///
+/// // Pods cannot be selected by their image
+/// assert!(FieldSelector::builder::<Pod>()
+///     .equal("spec.containers.image", "nginx")
+///     .build()
+///     .is_err());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldSelector {
+    requirements: Vec<FieldRequirement>,
+}
+
+impl FieldSelector {
+    /// Create a [`FieldSelectorBuilder`] for the Kubernetes type `K`
+    pub fn builder<K: Resource>() -> FieldSelectorBuilder {
+        FieldSelectorBuilder::new(K::GROUP, K::KIND)
+    }
+
+    /// Create a [`FieldSelectorBuilder`] for the kind identified by
+    /// `api_version` and `kind`, e.g. `v1` and `Pod`
+    pub fn builder_for(api_version: &str, kind: &str) -> FieldSelectorBuilder {
+        let group = api_version
+            .split_once('/')
+            .map(|(group, _)| group)
+            .unwrap_or_default();
+        FieldSelectorBuilder::new(group, kind)
+    }
+
+    /// The requirements of the selector, in the order they have been added
+    pub fn requirements(&self) -> &[FieldRequirement] {
+        &self.requirements
+    }
+
+    /// Tells whether the selector has no requirements, hence it matches
+    /// everything
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+}
+
+impl fmt::Display for FieldSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let requirements: Vec<String> = self.requirements.iter().map(|r| r.to_string()).collect();
+        write!(f, "{}", requirements.join(","))
+    }
+}
+
+/// Builds a [`FieldSelector`], ensuring only the fields supported by the
+/// kind are used. The validation errors are reported by
+/// [`FieldSelectorBuilder::build`]
+#[derive(Debug)]
+pub struct FieldSelectorBuilder {
+    group: String,
+    kind: String,
+    /// The requirements, each one with a flag telling whether its field must
+    /// be validated
+    requirements: Vec<(FieldRequirement, bool)>,
+}
+
+impl FieldSelectorBuilder {
+    fn new(group: &str, kind: &str) -> Self {
+        FieldSelectorBuilder {
+            group: group.to_string(),
+            kind: kind.to_string(),
+            requirements: Vec::new(),
+        }
+    }
+
+    fn requirement(
+        mut self,
+        field: &str,
+        operator: FieldOperator,
+        value: &str,
+        checked: bool,
+    ) -> Self {
+        self.requirements.push((
+            FieldRequirement {
+                field: field.to_string(),
+                operator,
+                value: value.to_string(),
+            },
+            checked,
+        ));
+        self
+    }
+
+    /// Require `field` to be equal to `value`, using the `=` operator
+    pub fn equal(self, field: &str, value: &str) -> Self {
+        self.requirement(field, FieldOperator::Equals, value, true)
+    }
+
+    /// Require `field` to be equal to `value`, using the `==` operator
+    pub fn double_equal(self, field: &str, value: &str) -> Self {
+        self.requirement(field, FieldOperator::DoubleEquals, value, true)
+    }
+
+    /// Require `field` to be different from `value`
+    pub fn not_equal(self, field: &str, value: &str) -> Self {
+        self.requirement(field, FieldOperator::NotEquals, value, true)
+    }
+
+    /// Add a requirement whose field is not validated by
+    /// [`FieldSelectorBuilder::build`]. Use it for the fields the SDK doesn't
+    /// know about, like the `selectableFields` declared by a
+    /// CustomResourceDefinition. Using a field not supported by the API
+    /// server makes the list request fail.
+    pub fn field_unchecked(self, field: &str, operator: FieldOperator, value: &str) -> Self {
+        self.requirement(field, operator, value, false)
+    }
+
+    /// Create the selector, failing when fields not supported by the kind
+    /// are used
+    pub fn build(self) -> Result<FieldSelector> {
+        let supported = supported_fields(&self.group, &self.kind);
+        let unsupported: Vec<&str> = self
+            .requirements
+            .iter()
+            .filter(|(_, checked)| *checked)
+            .map(|(r, _)| r.field.as_str())
+            .filter(|field| !supported.contains(field))
+            .collect();
+        if !unsupported.is_empty() {
+            return Err(anyhow!(
+                "field selector of {} cannot use {}, supported fields are: {}",
+                self.kind,
+                unsupported.join(", "),
+                supported.join(", ")
+            ));
+        }
+
+        Ok(FieldSelector {
+            requirements: self.requirements.into_iter().map(|(r, _)| r).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::apps::v1::ReplicaSet;
+    use k8s_openapi::api::core::v1::{ConfigMap, Pod};
+
+    #[test]
+    fn build_selectors() {
+        let selector = FieldSelector::builder::<Pod>()
+            .equal("metadata.namespace", "default")
+            .double_equal("status.phase", "Running")
+            .not_equal("spec.nodeName", "")
+            .build()
+            .unwrap();
+        assert_eq!(
+            selector.to_string(),
+            "metadata.namespace=default,status.phase==Running,spec.nodeName!="
+        );
+
+        let selector = FieldSelector::builder::<ReplicaSet>()
+            .equal("status.replicas", "0")
+            .build()
+            .unwrap();
+        assert_eq!(selector.to_string(), "status.replicas=0");
+
+        assert!(FieldSelector::builder::<Pod>().build().unwrap().is_empty());
+    }
+
+    #[test]
+    fn values_are_escaped() {
+        let selector = FieldSelector::builder::<ConfigMap>()
+            .equal("metadata.name", r"a=b,c\d")
+            .build()
+            .unwrap();
+        assert_eq!(selector.to_string(), r"metadata.name=a\=b\,c\\d");
+    }
+
+    #[test]
+    fn unsupported_fields() {
+        let err = FieldSelector::builder::<ConfigMap>()
+            .equal("metadata.name", "config")
+            .equal("data.key", "value")
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "field selector of ConfigMap cannot use data.key, supported fields are: metadata.name, metadata.namespace"
+        );
+
+        // status.replicas is supported only by the ReplicaSets of the apps group
+        assert!(FieldSelector::builder_for("apps/v1", "ReplicaSet")
+            .equal("status.replicas", "1")
+            .build()
+            .is_ok());
+        assert!(FieldSelector::builder_for("example.com/v1", "ReplicaSet")
+            .equal("status.replicas", "1")
+            .build()
+            .is_err());
+        assert!(FieldSelector::builder_for("v1", "Pod")
+            .not_equal("status.phase", "Failed")
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn unchecked_fields() {
+        let selector = FieldSelector::builder_for("example.com/v1", "Widget")
+            .equal("metadata.namespace", "default")
+            .field_unchecked("spec.color", FieldOperator::NotEquals, "red")
+            .build()
+            .unwrap();
+        assert_eq!(
+            selector.to_string(),
+            "metadata.namespace=default,spec.color!=red"
+        );
+
+        // the other fields are still validated
+        assert!(FieldSelector::builder_for("example.com/v1", "Widget")
+            .field_unchecked("spec.color", FieldOperator::Equals, "red")
+            .equal("spec.size", "large")
+            .build()
+            .is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::host_capabilities::crypto_v1::{
     CertificateVerificationRequest, CertificateVerificationResponse,
 };
+use crate::host_capabilities::transport::host_call;
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 
@@ -75,9 +76,8 @@ pub fn verify_cert(
             e
         )
     })?;
-    let response_raw =
-        wapc_guest::host_call("kubewarden", "crypto", "v1/is_certificate_trusted", &msg)
-            .map_err(|e| anyhow!("{}", e))?;
+    let response_raw = host_call("kubewarden", "crypto", "v1/is_certificate_trusted", &msg)
+        .map_err(|e| anyhow!("{}", e))?;
 
     let response: CertificateVerificationResponse = serde_json::from_slice(&response_raw)?;
     match response.trusted {
@@ -1,9 +1,11 @@
+use crate::host_capabilities::transport::host_call;
 use anyhow::{anyhow, Result};
-use serde::{Deserialize, Serialize};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ListMeta, ObjectMeta};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 /// Describe the set of parameters used by the `list_resources_by_namespace`
 /// function.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ListResourcesByNamespaceRequest {
     /// apiVersion of the resource (v1 for core group, groupName/groupVersions for other).
     pub api_version: String,
@@ -12,10 +14,12 @@ pub struct ListResourcesByNamespaceRequest {
     /// Namespace scoping the search
     pub namespace: String,
     /// A selector to restrict the list of returned objects by their labels.
-    /// Defaults to everything if `None`
+    /// Defaults to everything if `None`. See [`crate::label_selector::Selector`]
+    /// to build it.
     pub label_selector: Option<String>,
     /// A selector to restrict the list of returned objects by their fields.
-    /// Defaults to everything if `None`
+    /// Defaults to everything if `None`. See [`crate::field_selector::FieldSelector`]
+    /// to build it.
     pub field_selector: Option<String>,
 }
 
@@ -28,40 +32,57 @@ pub fn list_resources_by_namespace<T>(
 where
     T: k8s_openapi::ListableResource + serde::de::DeserializeOwned + Clone,
 {
-    let msg = serde_json::to_vec(req).map_err(|e| {
-        anyhow!(
-            "error serializing the list resources by namespace request: {}",
-            e
-        )
-    })?;
-    let response_raw = wapc_guest::host_call(
-        "kubewarden",
-        "kubernetes",
+    list(
         "list_resources_by_namespace",
-        &msg,
+        "list resources by namespace",
+        req,
     )
-    .map_err(|e| anyhow!("{}", e))?;
+}
 
-    serde_json::from_slice(&response_raw).map_err(|e| {
-        anyhow!(
-            "error deserializing list resources by namespace response into Kubernetes resource: {:?}",
-            e
-        )
-    })
+/// Iterate over the Kubernetes resources defined inside of the given
+/// namespace.
+///
+/// Use [`PartialObjectMetadata`] as `T` when only the metadata of the objects
+/// is needed, and [`DynamicObject`] for the resources whose type is not known
+/// at build time.
+///
+/// Note: the host returns all the objects with a single call, see
+/// [`ResourceIterator`]
+/// Note: cannot be used for cluster-wide resources
+pub fn iter_resources_by_namespace<T>(req: &ListResourcesByNamespaceRequest) -> ResourceIterator<T>
+where
+    T: DeserializeOwned,
+{
+    ResourceIterator::new(list(
+        "list_resources_by_namespace",
+        "list resources by namespace",
+        req,
+    ))
+}
+
+/// Count the Kubernetes resources defined inside of the given namespace, see
+/// [`iter_resources_by_namespace`].
+///
+/// Note: only the metadata of the objects is decoded, but the host still
+/// transfers the full objects
+pub fn count_resources_by_namespace(req: &ListResourcesByNamespaceRequest) -> Result<usize> {
+    count(iter_resources_by_namespace::<PartialObjectMetadata>(req))
 }
 
 /// Describe the set of parameters used by the `list_all_resources` function.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ListAllResourcesRequest {
     /// apiVersion of the resource (v1 for core group, groupName/groupVersions for other).
     pub api_version: String,
     /// Singular PascalCase name of the resource
     pub kind: String,
     /// A selector to restrict the list of returned objects by their labels.
-    /// Defaults to everything if `None`
+    /// Defaults to everything if `None`. See [`crate::label_selector::Selector`]
+    /// to build it.
     pub label_selector: Option<String>,
     /// A selector to restrict the list of returned objects by their fields.
-    /// Defaults to everything if `None`
+    /// Defaults to everything if `None`. See [`crate::field_selector::FieldSelector`]
+    /// to build it.
     pub field_selector: Option<String>,
 }
 
@@ -71,20 +92,220 @@ pub fn list_all_resources<T>(req: &ListAllResourcesRequest) -> Result<k8s_openap
 where
     T: k8s_openapi::ListableResource + serde::de::DeserializeOwned + Clone,
 {
+    list("list_resources_all", "list all resources", req)
+}
+
+/// Iterate over all the Kubernetes resources defined inside of the cluster.
+///
+/// Use [`PartialObjectMetadata`] as `T` when only the metadata of the objects
+/// is needed, and [`DynamicObject`] for the resources whose type is not known
+/// at build time.
+///
+/// Note: the host returns all the objects with a single call, see
+/// [`ResourceIterator`]
+pub fn iter_all_resources<T>(req: &ListAllResourcesRequest) -> ResourceIterator<T>
+where
+    T: DeserializeOwned,
+{
+    ResourceIterator::new(list("list_resources_all", "list all resources", req))
+}
+
+/// Count all the Kubernetes resources defined inside of the cluster, see
+/// [`iter_all_resources`].
+///
+/// Note: only the metadata of the objects is decoded, but the host still
+/// transfers the full objects
+pub fn count_all_resources(req: &ListAllResourcesRequest) -> Result<usize> {
+    count(iter_all_resources::<PartialObjectMetadata>(req))
+}
+
+/// Perform a list host call
+/// # Arguments
+/// * `operation` - the host capability operation
+/// * `description` - the description of the operation, used by the errors
+/// * `req` - the request
+fn list<R: Serialize, L: DeserializeOwned>(
+    operation: &str,
+    description: &str,
+    req: &R,
+) -> Result<L> {
     let msg = serde_json::to_vec(req)
-        .map_err(|e| anyhow!("error serializing the list all resources request: {}", e))?;
+        .map_err(|e| anyhow!("error serializing the {} request: {}", description, e))?;
     let response_raw =
-        wapc_guest::host_call("kubewarden", "kubernetes", "list_resources_all", &msg)
-            .map_err(|e| anyhow!("{}", e))?;
+        host_call("kubewarden", "kubernetes", operation, &msg).map_err(|e| anyhow!("{}", e))?;
 
     serde_json::from_slice(&response_raw).map_err(|e| {
         anyhow!(
-            "error deserializing list all resources response into Kubernetes resource: {:?}",
+            "error deserializing {} response into Kubernetes resource: {:?}",
+            description,
             e
         )
     })
 }
 
+fn count<T>(iter: ResourceIterator<T>) -> Result<usize> {
+    iter.map(|item| item.map(|_| 1)).sum()
+}
+
+/// The metadata of a Kubernetes object, without its spec and status. Useful
+/// to list objects when only their names, labels or annotations are needed.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialObjectMetadata {
+    #[serde(default)]
+    pub api_version: String,
+    #[serde(default)]
+    pub kind: String,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+}
+
+/// The objects returned by a list host call, decoded even when `T` is not a
+/// `k8s_openapi::ListableResource`, like [`DynamicObject`] and
+/// [`PartialObjectMetadata`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ObjectList<T> {
+    /// The list metadata
+    #[serde(default)]
+    pub metadata: ListMeta,
+    #[serde(default = "Vec::new")]
+    pub items: Vec<T>,
+}
+
+/// A Kubernetes object whose type is not known at build time, like a custom
+/// resource. Everything besides `apiVersion`, `kind` and `metadata` is kept
+/// inside of `data`.
+///
+/// The items of a list usually don't have `apiVersion` and `kind`, those
+/// fields are left empty in that case, and they are omitted when the object
+/// is serialized.
+///
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::host_capabilities::kubernetes::DynamicObject;
+///
+/// let certificate: DynamicObject = serde_json::from_value(serde_json::json!({
+///     "apiVersion": "cert-manager.io/v1",
+///     "kind": "Certificate",
+///     "metadata": {"name": "example-com", "namespace": "default"},
+///     "spec": {"secretName": "example-com-tls", "dnsNames": ["example.com"]},
+/// }))
+/// .unwrap();
+///
+/// assert_eq!(certificate.metadata.name.as_deref(), Some("example-com"));
+/// assert_eq!(certificate.data["spec"]["secretName"], "example-com-tls");
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DynamicObject {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub api_version: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub kind: String,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    /// All the other fields of the object, like `spec` and `status`
+    #[serde(flatten)]
+    pub data: serde_json::Value,
+}
+
+impl DynamicObject {
+    /// Create an object without data
+    pub fn new(api_version: &str, kind: &str, metadata: ObjectMeta) -> Self {
+        DynamicObject {
+            api_version: api_version.to_string(),
+            kind: kind.to_string(),
+            metadata,
+            data: serde_json::Value::Object(Default::default()),
+        }
+    }
+
+    /// Convert the object into the type `T`, e.g. a k8s-openapi type
+    pub fn try_parse<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::to_value(self)
+            .and_then(serde_json::from_value)
+            .map_err(|e| {
+                anyhow!(
+                    "cannot convert {} {} into the requested type: {}",
+                    self.kind,
+                    self.metadata.name.as_deref().unwrap_or_default(),
+                    e
+                )
+            })
+    }
+}
+
+/// Get a Kubernetes resource whose type is not known at build time, like a
+/// custom resource. The resource is identified by the `api_version` and
+/// `kind` of the request.
+pub fn get_dynamic_resource(req: &GetResourceRequest) -> Result<DynamicObject> {
+    get_resource(req)
+}
+
+/// Like [`list_resources_by_namespace`], but for resources whose type is not
+/// known at build time, like custom resources.
+/// Use [`iter_resources_by_namespace`] with [`DynamicObject`] to iterate
+/// over the objects.
+/// Note: cannot be used for cluster-wide resources
+pub fn list_dynamic_resources_by_namespace(
+    req: &ListResourcesByNamespaceRequest,
+) -> Result<ObjectList<DynamicObject>> {
+    list(
+        "list_resources_by_namespace",
+        "list resources by namespace",
+        req,
+    )
+}
+
+/// Like [`list_all_resources`], but for resources whose type is not known at
+/// build time, like custom resources.
+/// Use [`iter_all_resources`] with [`DynamicObject`] to iterate over the
+/// objects.
+pub fn list_all_dynamic_resources(
+    req: &ListAllResourcesRequest,
+) -> Result<ObjectList<DynamicObject>> {
+    list("list_resources_all", "list all resources", req)
+}
+
+/// Iterator over the objects returned by a list host call.
+/// See [`iter_resources_by_namespace`] and [`iter_all_resources`].
+///
+/// This is a convenience over a single response: pagination is not part of
+/// the host protocol, hence all the objects are returned by one host call
+/// and they are all kept in memory. A failed host call is reported as the
+/// only item of the iteration.
+pub struct ResourceIterator<T> {
+    items: std::vec::IntoIter<T>,
+    error: Option<anyhow::Error>,
+}
+
+impl<T> ResourceIterator<T> {
+    fn new(list: Result<ObjectList<T>>) -> Self {
+        match list {
+            Ok(list) => ResourceIterator {
+                items: list.items.into_iter(),
+                error: None,
+            },
+            Err(e) => ResourceIterator {
+                items: Vec::new().into_iter(),
+                error: Some(e),
+            },
+        }
+    }
+}
+
+impl<T> Iterator for ResourceIterator<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+        self.items.next().map(Ok)
+    }
+}
+
 /// Describe the set of parameters used by the `get_resource` function.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GetResourceRequest {
@@ -112,7 +333,7 @@ where
 {
     let msg = serde_json::to_vec(req)
         .map_err(|e| anyhow!("error serializing the get resource request: {}", e))?;
-    let response_raw = wapc_guest::host_call("kubewarden", "kubernetes", "get_resource", &msg)
+    let response_raw = host_call("kubewarden", "kubernetes", "get_resource", &msg)
         .map_err(|e| anyhow!("{}", e))?;
 
     serde_json::from_slice(&response_raw).map_err(|e| {
@@ -122,3 +343,263 @@ where
         )
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::transport::{self, InMemoryTransport};
+    use k8s_openapi::api::core::v1::Pod;
+    use serde_json::json;
+    use std::rc::Rc;
+
+    /// Answer the list calls with `total` pods named `pod-<index>`
+    fn pods(total: usize) -> InMemoryTransport {
+        let items: Vec<serde_json::Value> = (0..total)
+            .map(|i| {
+                json!({
+                    "apiVersion": "v1",
+                    "kind": "Pod",
+                    "metadata": {"name": format!("pod-{}", i), "labels": {"index": i.to_string()}},
+                    "spec": {"containers": [{"name": "nginx", "image": "nginx"}]},
+                })
+            })
+            .collect();
+        let list = json!({
+            "apiVersion": "v1",
+            "kind": "PodList",
+            "metadata": {},
+            "items": items,
+        });
+        InMemoryTransport::new()
+            .respond_with("kubernetes", "list_resources_by_namespace", &list)
+            .respond_with("kubernetes", "list_resources_all", &list)
+    }
+
+    #[test]
+    fn iterate_over_resources() {
+        let transport = Rc::new(pods(3));
+        let _guard = transport::install(transport.clone());
+
+        let req = ListResourcesByNamespaceRequest {
+            api_version: "v1".to_string(),
+            kind: "Pod".to_string(),
+            namespace: "default".to_string(),
+            ..Default::default()
+        };
+        let names: Vec<String> = iter_resources_by_namespace::<Pod>(&req)
+            .map(|pod| pod.unwrap().metadata.name.unwrap())
+            .collect();
+
+        assert_eq!(names, vec!["pod-0", "pod-1", "pod-2"]);
+        assert_eq!(transport.calls().len(), 1);
+        let request: serde_json::Value =
+            serde_json::from_slice(&transport.calls()[0].payload).unwrap();
+        assert_eq!(request["namespace"], "default");
+    }
+
+    #[test]
+    fn iterate_over_metadata() {
+        let _guard = transport::install(pods(150));
+
+        let req = ListAllResourcesRequest {
+            api_version: "v1".to_string(),
+            kind: "Pod".to_string(),
+            ..Default::default()
+        };
+        let last = iter_all_resources::<PartialObjectMetadata>(&req)
+            .last()
+            .unwrap()
+            .unwrap();
+        assert_eq!(last.metadata.name, Some("pod-149".to_string()));
+        assert_eq!(
+            last.metadata.labels.unwrap().get("index"),
+            Some(&"149".to_string())
+        );
+
+        assert_eq!(count_all_resources(&req).unwrap(), 150);
+    }
+
+    #[test]
+    fn iteration_stops_on_error() {
+        let _guard = transport::install(InMemoryTransport::new().fail_with(
+            "kubernetes",
+            "list_resources_by_namespace",
+            "forbidden",
+        ));
+
+        let req = ListResourcesByNamespaceRequest {
+            api_version: "v1".to_string(),
+            kind: "Pod".to_string(),
+            namespace: "default".to_string(),
+            ..Default::default()
+        };
+        let mut iter = iter_resources_by_namespace::<Pod>(&req);
+        assert_eq!(iter.next().unwrap().unwrap_err().to_string(), "forbidden");
+        assert!(iter.next().is_none());
+        assert!(count_resources_by_namespace(&req).is_err());
+    }
+
+    #[test]
+    fn iterate_over_empty_list() {
+        let _guard = transport::install(pods(0));
+
+        let req = ListAllResourcesRequest {
+            api_version: "v1".to_string(),
+            kind: "Pod".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(count_all_resources(&req).unwrap(), 0);
+    }
+
+    #[test]
+    fn list_requests_serialization() {
+        let req = ListAllResourcesRequest {
+            api_version: "v1".to_string(),
+            kind: "Pod".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&req).unwrap(),
+            json!({
+                "api_version": "v1",
+                "kind": "Pod",
+                "label_selector": null,
+                "field_selector": null,
+            })
+        );
+    }
+
+    fn certificate(name: &str) -> serde_json::Value {
+        json!({
+            "apiVersion": "cert-manager.io/v1",
+            "kind": "Certificate",
+            "metadata": {"name": name, "namespace": "default"},
+            "spec": {"secretName": format!("{}-tls", name)},
+            "status": {"conditions": [{"type": "Ready", "status": "True"}]},
+        })
+    }
+
+    #[test]
+    fn dynamic_object_round_trip() {
+        let object: DynamicObject = serde_json::from_value(certificate("example-com")).unwrap();
+        assert_eq!(object.api_version, "cert-manager.io/v1");
+        assert_eq!(object.kind, "Certificate");
+        assert_eq!(object.metadata.namespace, Some("default".to_string()));
+        assert_eq!(object.data["status"]["conditions"][0]["type"], "Ready");
+        assert!(object.data.get("metadata").is_none());
+        assert_eq!(
+            serde_json::to_value(&object).unwrap(),
+            certificate("example-com")
+        );
+
+        let pod = DynamicObject::new(
+            "v1",
+            "Pod",
+            ObjectMeta {
+                name: Some("nginx".to_string()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            serde_json::to_value(&pod).unwrap(),
+            json!({"apiVersion": "v1", "kind": "Pod", "metadata": {"name": "nginx"}})
+        );
+        let pod: Pod = pod.try_parse().unwrap();
+        assert_eq!(pod.metadata.name, Some("nginx".to_string()));
+    }
+
+    #[test]
+    fn dynamic_resources() {
+        let transport = Rc::new(
+            InMemoryTransport::new()
+                .respond_with("kubernetes", "get_resource", &certificate("example-com"))
+                .respond_with(
+                    "kubernetes",
+                    "list_resources_all",
+                    &json!({
+                        "apiVersion": "cert-manager.io/v1",
+                        "kind": "CertificateList",
+                        "metadata": {"resourceVersion": "42"},
+                        "items": [certificate("a"), certificate("b")],
+                    }),
+                ),
+        );
+        let _guard = transport::install(transport.clone());
+
+        let certificate = get_dynamic_resource(&GetResourceRequest {
+            api_version: "cert-manager.io/v1".to_string(),
+            kind: "Certificate".to_string(),
+            name: "example-com".to_string(),
+            namespace: Some("default".to_string()),
+            disable_cache: false,
+        })
+        .unwrap();
+        assert_eq!(certificate.data["spec"]["secretName"], "example-com-tls");
+
+        let list = list_all_dynamic_resources(&ListAllResourcesRequest {
+            api_version: "cert-manager.io/v1".to_string(),
+            kind: "Certificate".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        let names: Vec<String> = list
+            .items
+            .into_iter()
+            .map(|c| c.metadata.name.unwrap())
+            .collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(list.metadata.resource_version, Some("42".to_string()));
+
+        let request: serde_json::Value =
+            serde_json::from_slice(&transport.calls()[1].payload).unwrap();
+        assert_eq!(request["kind"], "Certificate");
+        assert_eq!(request["api_version"], "cert-manager.io/v1");
+    }
+
+    #[test]
+    fn dynamic_list_items_without_type_meta() {
+        let transport = InMemoryTransport::new().respond_with(
+            "kubernetes",
+            "list_resources_by_namespace",
+            &json!({
+                "apiVersion": "v1",
+                "kind": "ConfigMapList",
+                "items": [{"metadata": {"name": "settings"}, "data": {"key": "value"}}],
+            }),
+        );
+        let _guard = transport::install(transport);
+        let list = list_dynamic_resources_by_namespace(&ListResourcesByNamespaceRequest {
+            api_version: "v1".to_string(),
+            kind: "ConfigMap".to_string(),
+            namespace: "default".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(list.items[0].kind, "");
+        assert_eq!(list.items[0].metadata.name, Some("settings".to_string()));
+        assert_eq!(list.items[0].data["data"]["key"], "value");
+    }
+
+    #[test]
+    fn parse_dynamic_object_without_type_meta() {
+        let object: DynamicObject = serde_json::from_value(json!({
+            "metadata": {"name": "nginx"},
+            "spec": {"containers": [{"name": "nginx", "image": "nginx"}]},
+        }))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&object).unwrap(),
+            json!({
+                "metadata": {"name": "nginx"},
+                "spec": {"containers": [{"name": "nginx", "image": "nginx"}]},
+            })
+        );
+
+        let pod: Pod = object.try_parse().unwrap();
+        assert_eq!(pod.metadata.name, Some("nginx".to_string()));
+        assert_eq!(
+            pod.spec.unwrap().containers[0].image,
+            Some("nginx".to_string())
+        );
+    }
+}
@@ -8,6 +8,7 @@ pub mod crypto;
 pub mod kubernetes;
 pub mod net;
 pub mod oci;
+pub mod transport;
 pub mod verification;
 
 /// SigstoreVerificationInputV1 is used for the v1/verify callback
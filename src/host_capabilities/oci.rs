@@ -1,3 +1,4 @@
+use crate::host_capabilities::transport;
 use anyhow::{anyhow, Result};
 use oci_spec::image::{ImageConfiguration, ImageIndex, ImageManifest};
 use serde::{Deserialize, Serialize};
@@ -5,6 +6,22 @@ use serde_json::json;
 #[cfg(test)]
 use tests::mock_wapc as wapc_guest;
 
+/// Perform a host call through the installed transport, falling back to waPC
+fn host_call(
+    binding: &str,
+    namespace: &str,
+    operation: &str,
+    payload: &[u8],
+) -> ::wapc_guest::CallResult {
+    transport::host_call_or(
+        wapc_guest::host_call,
+        binding,
+        namespace,
+        operation,
+        payload,
+    )
+}
+
 /// Response to manifest digest request
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ManifestDigestResponse {
@@ -37,7 +54,7 @@ pub fn get_manifest_digest(image: &str) -> Result<ManifestDigestResponse> {
     let req = json!(image);
     let msg = serde_json::to_vec(&req)
         .map_err(|e| anyhow!("error serializing the validation request: {}", e))?;
-    let response_raw = wapc_guest::host_call("kubewarden", "oci", "v1/manifest_digest", &msg)
+    let response_raw = host_call("kubewarden", "oci", "v1/manifest_digest", &msg)
         .map_err(|e| anyhow!("error invoking wapc oci.manifest_digest: {:?}", e))?;
 
     let response: ManifestDigestResponse = serde_json::from_slice(&response_raw)?;
@@ -50,7 +67,7 @@ pub fn get_manifest(image: &str) -> Result<OciManifestResponse> {
     let req = json!(image);
     let msg = serde_json::to_vec(&req)
         .map_err(|e| anyhow!("error serializing the validation request: {}", e))?;
-    let response_raw = wapc_guest::host_call("kubewarden", "oci", "v1/oci_manifest", &msg)
+    let response_raw = host_call("kubewarden", "oci", "v1/oci_manifest", &msg)
         .map_err(|e| anyhow!("error invoking wapc oci.manifest_digest: {:?}", e))?;
     let response: OciManifestResponse = serde_json::from_slice(&response_raw)?;
     Ok(response)
@@ -61,9 +78,8 @@ pub fn get_manifest_and_config(image: &str) -> Result<OciManifestAndConfigRespon
     let req = json!(image);
     let msg = serde_json::to_vec(&req)
         .map_err(|e| anyhow!("error serializing the validation request: {}", e))?;
-    let response_raw =
-        wapc_guest::host_call("kubewarden", "oci", "v1/oci_manifest_config", &msg)
-            .map_err(|e| anyhow!("error invoking wapc oci.manifest_and_config: {:?}", e))?;
+    let response_raw = host_call("kubewarden", "oci", "v1/oci_manifest_config", &msg)
+        .map_err(|e| anyhow!("error invoking wapc oci.manifest_and_config: {:?}", e))?;
 
     let response: OciManifestAndConfigResponse = serde_json::from_slice(&response_raw)?;
 
@@ -349,4 +365,80 @@ mod tests {
         assert_eq!(response.manifest, create_oci_image_manifest());
         assert_eq!(response.digest, "sha256:983");
     }
+
+    mod in_memory_transport {
+        use super::*;
+        use crate::host_capabilities::transport::{self, HostCall, InMemoryTransport};
+        use std::rc::Rc;
+
+        fn assert_single_call(transport: &InMemoryTransport, operation: &str) {
+            assert_eq!(
+                transport.calls(),
+                vec![HostCall {
+                    binding: "kubewarden".to_string(),
+                    namespace: "oci".to_string(),
+                    operation: operation.to_string(),
+                    payload: b"\"ghcr.io/kubewarden/policy-server:latest\"".to_vec(),
+                }]
+            );
+        }
+
+        #[test]
+        fn verify_oci_image_manifest() {
+            let transport = Rc::new(InMemoryTransport::new().respond_with(
+                "oci",
+                "v1/oci_manifest",
+                &create_oci_image_manifest(),
+            ));
+            let _guard = transport::install(transport.clone());
+            let response = get_manifest("ghcr.io/kubewarden/policy-server:latest")
+                .expect("failed to get oci manifest reponse");
+            assert_single_call(&transport, "v1/oci_manifest");
+            match response {
+                OciManifestResponse::Image(image) => {
+                    assert_eq!(*image, create_oci_image_manifest());
+                }
+                OciManifestResponse::ImageIndex(_) => panic!("Invalid oci manifest type returned"),
+            }
+        }
+
+        #[test]
+        fn verify_oci_index_image_manifest() {
+            let transport = Rc::new(InMemoryTransport::new().respond_with(
+                "oci",
+                "v1/oci_manifest",
+                &create_oci_index_image_manifest(),
+            ));
+            let _guard = transport::install(transport.clone());
+            let response = get_manifest("ghcr.io/kubewarden/policy-server:latest")
+                .expect("failed to get oci manifest reponse");
+            assert_single_call(&transport, "v1/oci_manifest");
+            match response {
+                OciManifestResponse::Image(_) => panic!("Invalid oci manifest type returned"),
+                OciManifestResponse::ImageIndex(image) => {
+                    assert_eq!(*image, create_oci_index_image_manifest());
+                }
+            }
+        }
+
+        #[test]
+        fn verify_oci_image_manifest_and_config() {
+            let transport = Rc::new(InMemoryTransport::new().respond_with(
+                "oci",
+                "v1/oci_manifest_config",
+                &OciManifestAndConfigResponse {
+                    manifest: create_oci_image_manifest(),
+                    digest: "sha256:983".to_owned(),
+                    config: create_oci_image_configuration(),
+                },
+            ));
+            let _guard = transport::install(transport.clone());
+            let response = get_manifest_and_config("ghcr.io/kubewarden/policy-server:latest")
+                .expect("failed to get oci manifest reponse");
+            assert_single_call(&transport, "v1/oci_manifest_config");
+            assert_eq!(response.config, create_oci_image_configuration());
+            assert_eq!(response.manifest, create_oci_image_manifest());
+            assert_eq!(response.digest, "sha256:983");
+        }
+    }
 }
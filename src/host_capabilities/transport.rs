@@ -0,0 +1,293 @@
+use anyhow::anyhow;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wapc_guest::CallResult;
+
+/// Channel used by the host capabilities to reach the policy host.
+///
+/// Policies running inside of the policy server use [`WapcTransport`], which is
+/// the default. Unit tests can install a different transport via [`install`],
+/// for example an [`InMemoryTransport`] answering with scripted responses.
+pub trait HostTransport {
+    /// Perform a host call
+    /// # Arguments
+    /// * `binding` - the waPC binding, always `kubewarden` for the host capabilities
+    /// * `namespace` - the capability namespace, e.g. `oci` or `kubernetes`
+    /// * `operation` - the operation to perform, e.g. `v1/manifest_digest`
+    /// * `payload` - the JSON encoded request
+    fn host_call(
+        &self,
+        binding: &str,
+        namespace: &str,
+        operation: &str,
+        payload: &[u8],
+    ) -> CallResult;
+}
+
+impl<T: HostTransport + ?Sized> HostTransport for Rc<T> {
+    fn host_call(
+        &self,
+        binding: &str,
+        namespace: &str,
+        operation: &str,
+        payload: &[u8],
+    ) -> CallResult {
+        (**self).host_call(binding, namespace, operation, payload)
+    }
+}
+
+/// Transport relying on the waPC protocol, used by policies running inside
+/// of the policy server
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WapcTransport;
+
+impl HostTransport for WapcTransport {
+    fn host_call(
+        &self,
+        binding: &str,
+        namespace: &str,
+        operation: &str,
+        payload: &[u8],
+    ) -> CallResult {
+        wapc_guest::host_call(binding, namespace, operation, payload)
+    }
+}
+
+thread_local! {
+    static TRANSPORT: RefCell<Option<Rc<dyn HostTransport>>> = const { RefCell::new(None) };
+}
+
+/// Use `transport` for all the host calls made by the current thread, until
+/// the returned guard is dropped. The previously installed transport is then
+/// restored.
+///
+/// The transport is bound to the current thread, hence tests running in
+/// parallel can each install their own transport.
+#[must_use = "the transport is uninstalled when the guard is dropped"]
+pub fn install(transport: impl HostTransport + 'static) -> TransportGuard {
+    let previous = TRANSPORT.with(|t| t.borrow_mut().replace(Rc::new(transport)));
+    TransportGuard { previous }
+}
+
+/// Restores the previous transport when dropped, see [`install`]
+pub struct TransportGuard {
+    previous: Option<Rc<dyn HostTransport>>,
+}
+
+impl Drop for TransportGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        TRANSPORT.with(|t| *t.borrow_mut() = previous);
+    }
+}
+
+/// Perform a host call using the installed transport, falling back to
+/// [`WapcTransport`]
+pub(crate) fn host_call(
+    binding: &str,
+    namespace: &str,
+    operation: &str,
+    payload: &[u8],
+) -> CallResult {
+    host_call_or(
+        wapc_guest::host_call,
+        binding,
+        namespace,
+        operation,
+        payload,
+    )
+}
+
+/// Perform a host call using the installed transport, falling back to
+/// `fallback`. Used by the modules whose unit tests mock `wapc_guest::host_call`
+pub(crate) fn host_call_or(
+    fallback: fn(&str, &str, &str, &[u8]) -> CallResult,
+    binding: &str,
+    namespace: &str,
+    operation: &str,
+    payload: &[u8],
+) -> CallResult {
+    // clone the transport, it could perform host calls on its own
+    let transport = TRANSPORT.with(|t| t.borrow().clone());
+    match transport {
+        Some(transport) => transport.host_call(binding, namespace, operation, payload),
+        None => fallback(binding, namespace, operation, payload),
+    }
+}
+
+/// A host call received by the [`InMemoryTransport`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostCall {
+    pub binding: String,
+    pub namespace: String,
+    pub operation: String,
+    pub payload: Vec<u8>,
+}
+
+type Responder = Box<dyn Fn(&[u8]) -> CallResult>;
+
+/// Transport answering host calls with scripted responses, meant to be used
+/// by the unit tests of policies. Calls made to operations without a response
+/// fail.
+///
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::host_capabilities::oci::{self, ManifestDigestResponse};
+/// use kubewarden_policy_sdk::host_capabilities::transport::{self, InMemoryTransport};
+/// use std::rc::Rc;
+///
+/// let transport = Rc::new(InMemoryTransport::new().respond_with(
+///     "oci",
+///     "v1/manifest_digest",
+///     &ManifestDigestResponse {
+///         digest: "sha256:983".to_string(),
+///     },
+/// ));
+/// let _guard = transport::install(transport.clone());
+///
+/// let response = oci::get_manifest_digest("busybox:latest").unwrap();
+/// assert_eq!(response.digest, "sha256:983");
+/// assert_eq!(transport.calls()[0].payload, br#""busybox:latest""#);
+/// ```
+#[derive(Default)]
+pub struct InMemoryTransport {
+    responders: HashMap<(String, String), Responder>,
+    calls: RefCell<Vec<HostCall>>,
+}
+
+impl InMemoryTransport {
+    /// Create a transport without responses
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer the calls made to `operation` of `namespace` by invoking `responder`
+    /// with the payload of the call
+    pub fn on<F>(mut self, namespace: &str, operation: &str, responder: F) -> Self
+    where
+        F: Fn(&[u8]) -> CallResult + 'static,
+    {
+        self.responders.insert(
+            (namespace.to_string(), operation.to_string()),
+            Box::new(responder),
+        );
+        self
+    }
+
+    /// Answer the calls made to `operation` of `namespace` with the JSON
+    /// representation of `response`
+    pub fn respond_with<R: Serialize>(
+        self,
+        namespace: &str,
+        operation: &str,
+        response: &R,
+    ) -> Self {
+        let response = serde_json::to_vec(response).map_err(|e| e.to_string());
+        self.on(namespace, operation, move |_| {
+            response
+                .clone()
+                .map_err(|e| anyhow!("cannot serialize scripted response: {}", e).into())
+        })
+    }
+
+    /// Make the calls to `operation` of `namespace` fail with `message`
+    pub fn fail_with(self, namespace: &str, operation: &str, message: &str) -> Self {
+        let message = message.to_string();
+        self.on(namespace, operation, move |_| {
+            Err(anyhow!("{}", message).into())
+        })
+    }
+
+    /// The host calls received so far, in order
+    pub fn calls(&self) -> Vec<HostCall> {
+        self.calls.borrow().clone()
+    }
+}
+
+impl HostTransport for InMemoryTransport {
+    fn host_call(
+        &self,
+        binding: &str,
+        namespace: &str,
+        operation: &str,
+        payload: &[u8],
+    ) -> CallResult {
+        self.calls.borrow_mut().push(HostCall {
+            binding: binding.to_string(),
+            namespace: namespace.to_string(),
+            operation: operation.to_string(),
+            payload: payload.to_vec(),
+        });
+
+        let responder = self
+            .responders
+            .get(&(namespace.to_string(), operation.to_string()))
+            .ok_or_else(|| {
+                anyhow!(
+                    "no response scripted for host call {}/{}",
+                    namespace,
+                    operation
+                )
+            })?;
+        responder(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripted_responses() {
+        let transport = Rc::new(
+            InMemoryTransport::new()
+                .on("net", "v1/dns_lookup_host", |payload| Ok(payload.to_vec()))
+                .fail_with("oci", "v1/manifest_digest", "registry unreachable"),
+        );
+        let _guard = install(transport.clone());
+
+        assert_eq!(
+            host_call("kubewarden", "net", "v1/dns_lookup_host", b"echo").unwrap(),
+            b"echo"
+        );
+        let err = host_call("kubewarden", "oci", "v1/manifest_digest", b"").unwrap_err();
+        assert_eq!(err.to_string(), "registry unreachable");
+        let err = host_call("kubewarden", "oci", "v1/oci_manifest", b"").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no response scripted for host call oci/v1/oci_manifest"
+        );
+
+        let operations: Vec<String> = transport.calls().into_iter().map(|c| c.operation).collect();
+        assert_eq!(
+            operations,
+            vec![
+                "v1/dns_lookup_host",
+                "v1/manifest_digest",
+                "v1/oci_manifest"
+            ]
+        );
+    }
+
+    #[test]
+    fn guard_restores_previous_transport() {
+        let outer = Rc::new(InMemoryTransport::new().respond_with("net", "op", &"outer"));
+        let _outer_guard = install(outer.clone());
+        {
+            let _inner_guard =
+                install(InMemoryTransport::new().respond_with("net", "op", &"inner"));
+            assert_eq!(
+                host_call("kubewarden", "net", "op", b"").unwrap(),
+                br#""inner""#
+            );
+        }
+        assert_eq!(
+            host_call("kubewarden", "net", "op", b"").unwrap(),
+            br#""outer""#
+        );
+        assert_eq!(outer.calls().len(), 1);
+    }
+}
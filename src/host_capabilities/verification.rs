@@ -1,3 +1,4 @@
+use crate::host_capabilities::transport;
 use crate::host_capabilities::SigstoreVerificationInputV2;
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
@@ -150,11 +151,28 @@ pub fn verify_certificate(
 
     verify(input)
 }
+
+/// Perform a host call through the installed transport, falling back to waPC
+fn host_call(
+    binding: &str,
+    namespace: &str,
+    operation: &str,
+    payload: &[u8],
+) -> ::wapc_guest::CallResult {
+    transport::host_call_or(
+        wapc_guest::host_call,
+        binding,
+        namespace,
+        operation,
+        payload,
+    )
+}
+
 fn verify(input: SigstoreVerificationInputV2) -> Result<VerificationResponse> {
     let msg = serde_json::to_vec(&input)
         .map_err(|e| anyhow!("error serializing the validation request: {}", e))?;
-    let response_raw = wapc_guest::host_call("kubewarden", "oci", "v2/verify", &msg)
-        .map_err(|e| anyhow!("{}", e))?;
+    let response_raw =
+        host_call("kubewarden", "oci", "v2/verify", &msg).map_err(|e| anyhow!("{}", e))?;
 
     let response: VerificationResponse = serde_json::from_slice(&response_raw)?;
 
@@ -356,4 +374,156 @@ mod tests {
 
         assert!(res.is_err())
     }
+
+    mod in_memory_transport {
+        use super::*;
+        use crate::host_capabilities::transport::{self, InMemoryTransport, TransportGuard};
+        use std::rc::Rc;
+
+        fn install(transport: InMemoryTransport) -> (Rc<InMemoryTransport>, TransportGuard) {
+            let transport = Rc::new(transport);
+            let guard = transport::install(transport.clone());
+            (transport, guard)
+        }
+
+        fn trusted() -> (Rc<InMemoryTransport>, TransportGuard) {
+            install(InMemoryTransport::new().respond_with(
+                "oci",
+                "v2/verify",
+                &VerificationResponse {
+                    is_trusted: true,
+                    digest: "digest".to_string(),
+                },
+            ))
+        }
+
+        fn not_trusted() -> (Rc<InMemoryTransport>, TransportGuard) {
+            install(InMemoryTransport::new().fail_with("oci", "v2/verify", "not trusted"))
+        }
+
+        fn assert_single_call(transport: &InMemoryTransport) {
+            let calls = transport.calls();
+            assert_eq!(calls.len(), 1, "{:?}", calls);
+            assert_eq!(calls[0].namespace, "oci");
+            assert_eq!(calls[0].operation, "v2/verify");
+        }
+
+        #[test]
+        fn verify_pub_keys_trusted() {
+            let (transport, _guard) = trusted();
+            let res = verify_pub_keys_image("image", vec!["key".to_string()], None);
+
+            assert_single_call(&transport);
+            assert!(res.unwrap().is_trusted)
+        }
+
+        #[test]
+        fn verify_pub_keys_not_trusted() {
+            let (transport, _guard) = not_trusted();
+            let res = verify_pub_keys_image("image", vec!["key".to_string()], None);
+
+            assert_single_call(&transport);
+            assert!(res.is_err())
+        }
+
+        #[test]
+        fn verify_keyless_trusted() {
+            let (transport, _guard) = trusted();
+            let res = verify_keyless_exact_match(
+                "image",
+                vec![KeylessInfo {
+                    subject: "subject".to_string(),
+                    issuer: "issuer".to_string(),
+                }],
+                None,
+            );
+
+            assert_single_call(&transport);
+            assert!(res.unwrap().is_trusted)
+        }
+
+        #[test]
+        fn verify_keyless_not_trusted() {
+            let (transport, _guard) = not_trusted();
+            let res = verify_keyless_exact_match(
+                "image",
+                vec![KeylessInfo {
+                    subject: "subject".to_string(),
+                    issuer: "issuer".to_string(),
+                }],
+                None,
+            );
+
+            assert_single_call(&transport);
+            assert!(res.is_err())
+        }
+
+        #[test]
+        fn verify_keyless_prefix_trusted() {
+            let (transport, _guard) = trusted();
+            let res = verify_keyless_prefix_match(
+                "image",
+                vec![KeylessPrefixInfo {
+                    url_prefix: "urlprefix".to_string(),
+                    issuer: "issuer".to_string(),
+                }],
+                None,
+            );
+
+            assert_single_call(&transport);
+            assert!(res.unwrap().is_trusted)
+        }
+
+        #[test]
+        fn verify_keyless_prefix_not_trusted() {
+            let (transport, _guard) = not_trusted();
+            let res = verify_keyless_prefix_match(
+                "image",
+                vec![KeylessPrefixInfo {
+                    url_prefix: "urlprefix".to_string(),
+                    issuer: "issuer".to_string(),
+                }],
+                None,
+            );
+
+            assert_single_call(&transport);
+            assert!(res.is_err())
+        }
+
+        #[test]
+        fn verify_keyless_github_actions_trusted() {
+            let (transport, _guard) = trusted();
+            let res = verify_keyless_github_actions("image", "owner".to_string(), None, None);
+
+            assert_single_call(&transport);
+            assert!(res.unwrap().is_trusted)
+        }
+
+        #[test]
+        fn verify_keyless_github_actions_not_trusted() {
+            let (transport, _guard) = not_trusted();
+            let res = verify_keyless_github_actions("image", "owner".to_string(), None, None);
+
+            assert_single_call(&transport);
+            assert!(res.is_err())
+        }
+
+        #[test]
+        fn verify_certificate_trusted() {
+            let (transport, _guard) = trusted();
+            let res = verify_certificate("image", "CERT".to_string(), None, true, None);
+
+            assert_single_call(&transport);
+            assert!(res.unwrap().is_trusted)
+        }
+
+        #[test]
+        fn verify_certificate_not_trusted() {
+            let (transport, _guard) = not_trusted();
+            let res = verify_certificate("image", "CERT".to_string(), None, true, None);
+
+            assert_single_call(&transport);
+            assert!(res.is_err())
+        }
+    }
 }
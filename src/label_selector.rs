@@ -0,0 +1,603 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{LabelSelector, LabelSelectorRequirement};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::names::{validate_label_value, validate_qualified_name};
+
+/// Operator of a label selector [`Requirement`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operator {
+    /// `key=value` or `key==value`
+    Equals,
+    /// `key!=value`, also matched by objects without the label
+    NotEquals,
+    /// `key in (a,b)`
+    In,
+    /// `key notin (a,b)`, also matched by objects without the label
+    NotIn,
+    /// `key`
+    Exists,
+    /// `!key`
+    DoesNotExist,
+}
+
+/// A single condition of a [`Selector`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    key: String,
+    operator: Operator,
+    values: BTreeSet<String>,
+}
+
+impl Requirement {
+    /// Create a requirement, ensuring the key and the values are valid and
+    /// that their number is the one expected by the operator
+    /// # Arguments
+    /// * `key` - the label key, a qualified name like `app.kubernetes.io/name`
+    /// * `operator` - the operator
+    /// * `values` - the values, exactly one for `Equals` and `NotEquals`, at
+    ///   least one for `In` and `NotIn`, none for `Exists` and `DoesNotExist`
+    pub fn new<I, V>(key: &str, operator: Operator, values: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        validate_qualified_name(key).map_err(|e| anyhow!("invalid label key: {}", e))?;
+        let values: BTreeSet<String> = values.into_iter().map(Into::into).collect();
+        for value in &values {
+            validate_label_value(value).map_err(|e| anyhow!("{}", e))?;
+        }
+
+        let valid_count = match operator {
+            Operator::Equals | Operator::NotEquals => values.len() == 1,
+            Operator::In | Operator::NotIn => !values.is_empty(),
+            Operator::Exists | Operator::DoesNotExist => values.is_empty(),
+        };
+        if !valid_count {
+            return Err(anyhow!(
+                "invalid number of values for the {:?} operator of '{}': {}",
+                operator,
+                key,
+                values.len()
+            ));
+        }
+
+        Ok(Requirement {
+            key: key.to_string(),
+            operator,
+            values,
+        })
+    }
+
+    /// The label key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The operator
+    pub fn operator(&self) -> Operator {
+        self.operator
+    }
+
+    /// The values, sorted
+    pub fn values(&self) -> &BTreeSet<String> {
+        &self.values
+    }
+
+    /// Tells whether the requirement is satisfied by `labels`
+    // `Option::is_none_or` would require Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        let value = labels.get(&self.key);
+        match self.operator {
+            Operator::Equals | Operator::In => value.is_some_and(|v| self.values.contains(v)),
+            Operator::NotEquals | Operator::NotIn => {
+                value.map_or(true, |v| !self.values.contains(v))
+            }
+            Operator::Exists => value.is_some(),
+            Operator::DoesNotExist => value.is_none(),
+        }
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = || {
+            self.values
+                .iter()
+                .cloned()
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+        match self.operator {
+            Operator::Equals => write!(f, "{}={}", self.key, values()),
+            Operator::NotEquals => write!(f, "{}!={}", self.key, values()),
+            Operator::In => write!(f, "{} in ({})", self.key, values()),
+            Operator::NotIn => write!(f, "{} notin ({})", self.key, values()),
+            Operator::Exists => write!(f, "{}", self.key),
+            Operator::DoesNotExist => write!(f, "!{}", self.key),
+        }
+    }
+}
+
+impl TryFrom<&LabelSelectorRequirement> for Requirement {
+    type Error = anyhow::Error;
+
+    fn try_from(requirement: &LabelSelectorRequirement) -> Result<Self> {
+        let operator = match requirement.operator.as_str() {
+            "In" => Operator::In,
+            "NotIn" => Operator::NotIn,
+            "Exists" => Operator::Exists,
+            "DoesNotExist" => Operator::DoesNotExist,
+            operator => {
+                return Err(anyhow!(
+                    "invalid operator '{}' of the requirement on '{}'",
+                    operator,
+                    requirement.key
+                ))
+            }
+        };
+        Requirement::new(
+            &requirement.key,
+            operator,
+            requirement.values.iter().flatten().cloned(),
+        )
+    }
+}
+
+/// A label selector: the labels of an object must satisfy all of its
+/// requirements. The empty selector matches everything.
+///
+/// A selector can be created from the `LabelSelector` type found inside of
+/// the Kubernetes resources, from the string syntax used by the API server
+/// or via a [`SelectorBuilder`]. Once converted to a string, it can be used as
+/// the `label_selector` of the requests made to the kubernetes host capability.
+///
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::label_selector::Selector;
+/// use std::collections::BTreeMap;
+///
+/// let selector = Selector::builder()
+///     .equal("app", "nginx")
+///     .in_values("environment", ["prod", "staging"])
+///     .does_not_exist("deprecated")
+///     .build()
+///     .unwrap();
+/// assert_eq!(
+///     selector.to_string(),
+///     "app=nginx,!deprecated,environment in (prod,staging)"
+/// );
+///
+/// let labels = BTreeMap::from([
+///     ("app".to_string(), "nginx".to_string()),
+///     ("environment".to_string(), "prod".to_string()),
+/// ]);
+/// assert!(selector.matches(&labels));
+/// assert_eq!(selector, "app=nginx,environment in (staging,prod),!deprecated".parse().unwrap());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selector {
+    requirements: Vec<Requirement>,
+}
+
+impl Selector {
+    /// Create a selector from its requirements. Like the API server does, the
+    /// requirements are sorted by key.
+    pub fn new(requirements: impl IntoIterator<Item = Requirement>) -> Self {
+        let mut requirements: Vec<Requirement> = requirements.into_iter().collect();
+        requirements.sort_by(|a, b| a.key.cmp(&b.key));
+        Selector { requirements }
+    }
+
+    /// Create a [`SelectorBuilder`]
+    pub fn builder() -> SelectorBuilder {
+        SelectorBuilder::default()
+    }
+
+    /// The requirements of the selector, sorted by key
+    pub fn requirements(&self) -> &[Requirement] {
+        &self.requirements
+    }
+
+    /// Tells whether the selector has no requirements, hence it matches
+    /// everything
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+
+    /// Tells whether `labels` satisfy all the requirements
+    pub fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        self.requirements.iter().all(|r| r.matches(labels))
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let requirements: Vec<String> = self.requirements.iter().map(|r| r.to_string()).collect();
+        write!(f, "{}", requirements.join(","))
+    }
+}
+
+impl TryFrom<&LabelSelector> for Selector {
+    type Error = anyhow::Error;
+
+    fn try_from(selector: &LabelSelector) -> Result<Self> {
+        let labels = selector
+            .match_labels
+            .iter()
+            .flatten()
+            .map(|(key, value)| Requirement::new(key, Operator::Equals, [value.as_str()]));
+        let expressions = selector
+            .match_expressions
+            .iter()
+            .flatten()
+            .map(Requirement::try_from);
+        let requirements = labels.chain(expressions).collect::<Result<Vec<_>>>()?;
+        Ok(Selector::new(requirements))
+    }
+}
+
+impl From<&Selector> for LabelSelector {
+    fn from(selector: &Selector) -> Self {
+        let mut match_labels = BTreeMap::new();
+        let mut match_expressions = Vec::new();
+        for requirement in &selector.requirements {
+            let (operator, values) = match requirement.operator {
+                Operator::Equals if !match_labels.contains_key(&requirement.key) => {
+                    let value = requirement
+                        .values
+                        .iter()
+                        .next()
+                        .cloned()
+                        .unwrap_or_default();
+                    match_labels.insert(requirement.key.clone(), value);
+                    continue;
+                }
+                Operator::Equals | Operator::In => ("In", Some(&requirement.values)),
+                Operator::NotEquals | Operator::NotIn => ("NotIn", Some(&requirement.values)),
+                Operator::Exists => ("Exists", None),
+                Operator::DoesNotExist => ("DoesNotExist", None),
+            };
+            match_expressions.push(LabelSelectorRequirement {
+                key: requirement.key.clone(),
+                operator: operator.to_string(),
+                values: values.map(|values| values.iter().cloned().collect()),
+            });
+        }
+
+        LabelSelector {
+            match_labels: (!match_labels.is_empty()).then_some(match_labels),
+            match_expressions: (!match_expressions.is_empty()).then_some(match_expressions),
+        }
+    }
+}
+
+impl FromStr for Selector {
+    type Err = anyhow::Error;
+
+    /// Parse the syntax used by the API server, e.g.
+    /// `app=nginx,tier!=frontend,environment in (prod,staging),!deprecated`
+    fn from_str(s: &str) -> Result<Self> {
+        let parse_error = |e: anyhow::Error| anyhow!("invalid label selector '{}': {}", s, e);
+        if s.trim().is_empty() {
+            return Ok(Selector::default());
+        }
+        let requirements = split_requirements(s)
+            .map_err(parse_error)?
+            .into_iter()
+            .map(parse_requirement)
+            .collect::<Result<Vec<_>>>()
+            .map_err(parse_error)?;
+        Ok(Selector::new(requirements))
+    }
+}
+
+/// Split the requirements of a selector string, the commas found between
+/// parentheses separate values
+fn split_requirements(s: &str) -> Result<Vec<&str>> {
+    let mut requirements = Vec::new();
+    let mut start = 0;
+    let mut inside_parentheses = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' if inside_parentheses => return Err(anyhow!("nested parentheses")),
+            '(' => inside_parentheses = true,
+            ')' if !inside_parentheses => return Err(anyhow!("unbalanced parentheses")),
+            ')' => inside_parentheses = false,
+            ',' if !inside_parentheses => {
+                requirements.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if inside_parentheses {
+        return Err(anyhow!("unbalanced parentheses"));
+    }
+    requirements.push(&s[start..]);
+    Ok(requirements)
+}
+
+fn parse_requirement(requirement: &str) -> Result<Requirement> {
+    let requirement = requirement.trim();
+    if requirement.is_empty() {
+        return Err(anyhow!("empty requirement"));
+    }
+
+    if let Some(key) = requirement.strip_prefix('!') {
+        return Requirement::new(key.trim(), Operator::DoesNotExist, Vec::<String>::new());
+    }
+
+    if let Some((head, values)) = requirement.split_once('(') {
+        let values = values.strip_suffix(')').ok_or_else(|| {
+            anyhow!(
+                "unexpected characters after the values of '{}'",
+                requirement
+            )
+        })?;
+        let values: Vec<&str> = match values.trim() {
+            "" => Vec::new(),
+            values => values.split(',').map(str::trim).collect(),
+        };
+        let operator = match head.split_whitespace().collect::<Vec<&str>>()[..] {
+            [key, "in"] => (key, Operator::In),
+            [key, "notin"] => (key, Operator::NotIn),
+            _ => {
+                return Err(anyhow!(
+                    "expected '<key> in (<values>)' or '<key> notin (<values>)', got '{}'",
+                    requirement
+                ))
+            }
+        };
+        return Requirement::new(operator.0, operator.1, values);
+    }
+
+    for (separator, operator) in [
+        ("!=", Operator::NotEquals),
+        ("==", Operator::Equals),
+        ("=", Operator::Equals),
+    ] {
+        if let Some((key, value)) = requirement.split_once(separator) {
+            return Requirement::new(key.trim(), operator, [value.trim()]);
+        }
+    }
+
+    if requirement.contains(['<', '>']) {
+        return Err(anyhow!(
+            "the '<' and '>' operators are not supported: '{}'",
+            requirement
+        ));
+    }
+    Requirement::new(requirement, Operator::Exists, Vec::<String>::new())
+}
+
+/// Tells whether `labels` are matched by the Kubernetes `selector`, an error
+/// is returned when the selector is not valid
+pub fn label_selector_matches(
+    selector: &LabelSelector,
+    labels: &BTreeMap<String, String>,
+) -> Result<bool> {
+    Ok(Selector::try_from(selector)?.matches(labels))
+}
+
+/// Builds a [`Selector`], the validation errors are reported by
+/// [`SelectorBuilder::build`]
+#[derive(Debug, Default)]
+pub struct SelectorBuilder {
+    requirements: Vec<Result<Requirement>>,
+}
+
+impl SelectorBuilder {
+    fn requirement<I, V>(mut self, key: &str, operator: Operator, values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        self.requirements
+            .push(Requirement::new(key, operator, values));
+        self
+    }
+
+    /// Require the label `key` to be set to `value`
+    pub fn equal(self, key: &str, value: &str) -> Self {
+        self.requirement(key, Operator::Equals, [value])
+    }
+
+    /// Require the label `key` not to be set to `value`
+    pub fn not_equal(self, key: &str, value: &str) -> Self {
+        self.requirement(key, Operator::NotEquals, [value])
+    }
+
+    /// Require the label `key` to be set to one of `values`
+    pub fn in_values<I, V>(self, key: &str, values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        self.requirement(key, Operator::In, values)
+    }
+
+    /// Require the label `key` not to be set to any of `values`
+    pub fn not_in_values<I, V>(self, key: &str, values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        self.requirement(key, Operator::NotIn, values)
+    }
+
+    /// Require the label `key` to be set
+    pub fn exists(self, key: &str) -> Self {
+        self.requirement(key, Operator::Exists, Vec::<String>::new())
+    }
+
+    /// Require the label `key` not to be set
+    pub fn does_not_exist(self, key: &str) -> Self {
+        self.requirement(key, Operator::DoesNotExist, Vec::<String>::new())
+    }
+
+    /// Create the selector, failing when any requirement is not valid
+    pub fn build(self) -> Result<Selector> {
+        let mut requirements = Vec::new();
+        let mut errors = Vec::new();
+        for requirement in self.requirements {
+            match requirement {
+                Ok(requirement) => requirements.push(requirement),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(anyhow!("invalid label selector: {}", errors.join("; ")));
+        }
+        Ok(Selector::new(requirements))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn labels(labels: &[(&str, &str)]) -> BTreeMap<String, String> {
+        labels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn label_selector_to_string() {
+        let selector: LabelSelector = serde_json::from_value(json!({
+            "matchLabels": {"app": "nginx"},
+            "matchExpressions": [
+                {"key": "tier", "operator": "NotIn", "values": ["frontend", "backend"]},
+                {"key": "environment", "operator": "In", "values": ["prod"]},
+                {"key": "deprecated", "operator": "DoesNotExist"},
+                {"key": "team", "operator": "Exists"},
+            ]
+        }))
+        .unwrap();
+
+        let selector = Selector::try_from(&selector).unwrap();
+        assert_eq!(
+            selector.to_string(),
+            "app=nginx,!deprecated,environment in (prod),team,tier notin (backend,frontend)"
+        );
+        assert_eq!(
+            Selector::try_from(&LabelSelector::default())
+                .unwrap()
+                .to_string(),
+            ""
+        );
+    }
+
+    #[test]
+    fn invalid_label_selectors() {
+        for selector in [
+            json!({"matchLabels": {"app": "-nginx"}}),
+            json!({"matchLabels": {"-app": "nginx"}}),
+            json!({"matchExpressions": [{"key": "tier", "operator": "Like", "values": ["a"]}]}),
+            json!({"matchExpressions": [{"key": "tier", "operator": "In"}]}),
+            json!({"matchExpressions": [{"key": "tier", "operator": "Exists", "values": ["a"]}]}),
+        ] {
+            let label_selector: LabelSelector = serde_json::from_value(selector.clone()).unwrap();
+            assert!(Selector::try_from(&label_selector).is_err(), "{}", selector);
+        }
+    }
+
+    #[test]
+    fn parse_selectors() {
+        let selector: Selector =
+            " app == nginx, tier!=frontend,environment in (prod, staging ),team,! deprecated"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            selector.to_string(),
+            "app=nginx,!deprecated,environment in (prod,staging),team,tier!=frontend"
+        );
+        assert_eq!(selector, selector.to_string().parse().unwrap());
+        assert!("".parse::<Selector>().unwrap().is_empty());
+
+        for invalid in [
+            "app=nginx,",
+            "app in prod",
+            "app in (prod",
+            "app in (prod))",
+            "app notin ()",
+            "app within (prod)",
+            "replicas>3",
+            "app=-nginx",
+            "app=nginx=1",
+        ] {
+            assert!(invalid.parse::<Selector>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn evaluate_selectors() {
+        let nginx = labels(&[("app", "nginx"), ("tier", "frontend")]);
+        for (selector, expected) in [
+            ("", true),
+            ("app=nginx", true),
+            ("app=apache", false),
+            ("app!=apache", true),
+            ("environment!=prod", true),
+            ("tier in (frontend,backend)", true),
+            ("tier notin (frontend,backend)", false),
+            ("environment notin (prod)", true),
+            ("environment in (prod)", false),
+            ("app,!environment", true),
+            ("!app", false),
+        ] {
+            let parsed: Selector = selector.parse().unwrap();
+            assert_eq!(parsed.matches(&nginx), expected, "{}", selector);
+        }
+    }
+
+    #[test]
+    fn round_trip_to_label_selector() {
+        let selector = Selector::builder()
+            .equal("app", "nginx")
+            .not_equal("tier", "frontend")
+            .exists("team")
+            .build()
+            .unwrap();
+
+        let label_selector = LabelSelector::from(&selector);
+        assert_eq!(
+            serde_json::to_value(&label_selector).unwrap(),
+            json!({
+                "matchLabels": {"app": "nginx"},
+                "matchExpressions": [
+                    {"key": "team", "operator": "Exists"},
+                    {"key": "tier", "operator": "NotIn", "values": ["frontend"]},
+                ]
+            })
+        );
+        // `!=` has no counterpart inside of LabelSelector
+        assert_eq!(
+            Selector::try_from(&label_selector).unwrap().to_string(),
+            "app=nginx,team,tier notin (frontend)"
+        );
+        assert!(label_selector_matches(
+            &label_selector,
+            &labels(&[("app", "nginx"), ("team", "a")])
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn builder_reports_all_errors() {
+        let err = Selector::builder()
+            .equal("app", "nginx")
+            .in_values("tier", Vec::<String>::new())
+            .exists("invalid key")
+            .build()
+            .unwrap_err();
+        assert_eq!(err.to_string().matches("; ").count(), 1, "{}", err);
+    }
+}
@@ -2,21 +2,36 @@
 
 use std::collections::HashMap;
 
-use anyhow::anyhow;
-
 pub use wapc_guest;
 
+pub mod admission_review;
+#[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+#[cfg(feature = "cluster-context")]
+pub mod containers;
+#[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+#[cfg(feature = "cluster-context")]
+pub mod field_selector;
 pub mod host_capabilities;
+#[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+#[cfg(feature = "cluster-context")]
+pub mod label_selector;
 pub mod logging;
 pub mod metadata;
+mod names;
 #[cfg(not(target_arch = "wasm32"))]
 mod non_wasm;
+pub mod patch;
+#[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+#[cfg(feature = "cluster-context")]
+pub mod pod_template;
 pub mod request;
 pub mod response;
+pub mod router;
 pub mod settings;
 pub mod test;
 
 use crate::metadata::ProtocolVersion;
+use crate::request::PolicyMode;
 #[cfg(feature = "cluster-context")]
 use crate::request::ValidationRequest;
 use crate::response::*;
@@ -28,37 +43,37 @@ pub mod crd;
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "cluster-context")] {
-        use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet};
-        use k8s_openapi::api::batch::v1::{CronJob, Job};
-        use k8s_openapi::api::core::v1::{Pod, PodSpec, ReplicationController};
-        use k8s_openapi::Resource;
+        use crate::pod_template::{PodTemplateAccessor, Workload, UNSUPPORTED_KIND_ERROR};
+        use k8s_openapi::api::core::v1::{PodSpec, PodTemplateSpec};
     }
 }
 
-/// Create an acceptance response
+/// Create an acceptance response.
+/// Use [`ValidationResponseBuilder::accept`] to return warnings and audit annotations too.
 pub fn accept_request() -> wapc_guest::CallResult {
-    Ok(serde_json::to_vec(&ValidationResponse {
-        accepted: true,
-        message: None,
-        code: None,
-        mutated_object: None,
-        audit_annotations: None,
-        warnings: None,
-    })?)
+    ValidationResponseBuilder::accept().build()
 }
 
-/// Create an acceptance response that mutates the original object
+/// Create an acceptance response that mutates the original object.
+/// Use [`ValidationResponseBuilder::mutate`] to return warnings and audit annotations too.
 /// # Arguments
 /// * `mutated_object` - the mutated Object
 pub fn mutate_request(mutated_object: serde_json::Value) -> wapc_guest::CallResult {
-    Ok(serde_json::to_vec(&ValidationResponse {
-        accepted: true,
-        message: None,
-        code: None,
-        mutated_object: Some(mutated_object),
-        audit_annotations: None,
-        warnings: None,
-    })?)
+    ValidationResponseBuilder::mutate(mutated_object).build()
+}
+
+/// Create an acceptance response that mutates the original object by
+/// applying a JSON Patch (RFC 6902) to it.
+/// The patch can be built by hand or computed with [`patch::diff`].
+/// # Arguments
+/// * `original_object` - the Object of the incoming request
+/// * `patch` - the JSON Patch to be applied to the original Object
+pub fn mutate_request_with_patch(
+    original_object: &serde_json::Value,
+    patch: &patch::Patch,
+) -> wapc_guest::CallResult {
+    let mutated_object = patch::apply(original_object, patch)?;
+    mutate_request(mutated_object)
 }
 
 #[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
@@ -72,85 +87,40 @@ pub fn mutate_pod_spec_from_request<T: std::default::Default>(
     validation_request: ValidationRequest<T>,
     pod_spec: PodSpec,
 ) -> wapc_guest::CallResult {
-    match validation_request.request.kind.kind.as_str() {
-        Deployment::KIND => {
-            let mut deployment =
-                serde_json::from_value::<Deployment>(validation_request.request.object.clone())?;
-            let mut deployment_spec = deployment.spec.unwrap_or_default();
-            deployment_spec.template.spec = Some(pod_spec);
-            deployment.spec = Some(deployment_spec);
-            mutate_request(serde_json::to_value(deployment)?)
-        }
-        ReplicaSet::KIND => {
-            let mut replicaset =
-                serde_json::from_value::<ReplicaSet>(validation_request.request.object.clone())?;
-            let mut replicaset_spec = replicaset.spec.unwrap_or_default();
-            let mut template = replicaset_spec.template.unwrap_or_default();
-            template.spec = Some(pod_spec);
-            replicaset_spec.template = Some(template);
-            replicaset.spec = Some(replicaset_spec);
-            mutate_request(serde_json::to_value(replicaset)?)
-        }
-        StatefulSet::KIND => {
-            let mut statefulset =
-                serde_json::from_value::<StatefulSet>(validation_request.request.object.clone())?;
-            let mut statefulset_spec = statefulset.spec.unwrap_or_default();
-            statefulset_spec.template.spec = Some(pod_spec);
-            statefulset.spec = Some(statefulset_spec);
-            mutate_request(serde_json::to_value(statefulset)?)
-        }
-        DaemonSet::KIND => {
-            let mut daemonset =
-                serde_json::from_value::<DaemonSet>(validation_request.request.object.clone())?;
-            let mut daemonset_spec = daemonset.spec.unwrap_or_default();
-            daemonset_spec.template.spec = Some(pod_spec);
-            daemonset.spec = Some(daemonset_spec);
-            mutate_request(serde_json::to_value(daemonset)?)
-        }
-        ReplicationController::KIND => {
-            let mut replication_controller = serde_json::from_value::<ReplicationController>(
-                validation_request.request.object.clone(),
-            )?;
-            let mut replication_controller_spec = replication_controller.spec.unwrap_or_default();
-            let mut template = replication_controller_spec.template.unwrap_or_default();
-            template.spec = Some(pod_spec);
-            replication_controller_spec.template = Some(template);
-            replication_controller.spec = Some(replication_controller_spec);
-            mutate_request(serde_json::to_value(replication_controller)?)
-        }
-        CronJob::KIND => {
-            let mut cronjob =
-                serde_json::from_value::<CronJob>(validation_request.request.object.clone())?;
-            let mut cronjob_spec = cronjob.spec.unwrap_or_default();
-            let mut job_template_spec = cronjob_spec.job_template;
-            let mut job_spec = job_template_spec.spec.unwrap_or_default();
-            let mut pod_template_spec = job_spec.template;
-            pod_template_spec.spec = Some(pod_spec);
-            job_spec.template = pod_template_spec;
-            job_template_spec.spec = Some(job_spec);
-            cronjob_spec.job_template = job_template_spec;
-            cronjob.spec = Some(cronjob_spec);
-            mutate_request(serde_json::to_value(cronjob)?)
+    let request = &validation_request.request;
+    match Workload::from_object(&request.kind.kind, &request.object)? {
+        Some(mut workload) => {
+            workload.set_pod_spec(pod_spec);
+            mutate_request(workload.to_value()?)
         }
-        Job::KIND => {
-            let mut job = serde_json::from_value::<Job>(validation_request.request.object.clone())?;
-            let mut job_spec = job.spec.unwrap_or_default();
-            job_spec.template.spec = Some(pod_spec);
-            job.spec = Some(job_spec);
-            mutate_request(serde_json::to_value(job)?)
-        }
-        Pod::KIND => {
-            let mut pod = serde_json::from_value::<Pod>(validation_request.request.object.clone())?;
-            pod.spec = Some(pod_spec);
-            mutate_request(serde_json::to_value(pod)?)
-        }
-        _ => {
-            reject_request(Some("Object should be one of these kinds: Deployment, ReplicaSet, StatefulSet, DaemonSet, ReplicationController, Job, CronJob, Pod".to_string()), None, None, None)
+        None => reject_request(Some(UNSUPPORTED_KIND_ERROR.to_string()), None, None, None),
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+#[cfg(feature = "cluster-context")]
+/// Update the pod template, metadata included, of the resource defined in the
+/// original object and create an acceptance response.
+/// For Pods, the metadata and the spec of the Pod are replaced.
+/// # Arguments
+/// * `validation_request` - the original admission request
+/// * `pod_template` - new PodTemplateSpec to be set in the response
+pub fn mutate_pod_template_from_request<T: std::default::Default>(
+    validation_request: ValidationRequest<T>,
+    pod_template: PodTemplateSpec,
+) -> wapc_guest::CallResult {
+    let request = &validation_request.request;
+    match Workload::from_object(&request.kind.kind, &request.object)? {
+        Some(mut workload) => {
+            workload.set_pod_template(pod_template);
+            mutate_request(workload.to_value()?)
         }
+        None => reject_request(Some(UNSUPPORTED_KIND_ERROR.to_string()), None, None, None),
     }
 }
 
-/// Create a rejection response
+/// Create a rejection response. Use [`reject_request_with_mode`] when the
+/// policy can run in monitor mode.
 /// # Arguments
 /// * `message` -  message shown to the user
 /// * `code` -  code shown to the user
@@ -162,14 +132,43 @@ pub fn reject_request(
     audit_annotations: Option<HashMap<String, String>>,
     warnings: Option<Vec<String>>,
 ) -> wapc_guest::CallResult {
-    Ok(serde_json::to_vec(&ValidationResponse {
-        accepted: false,
-        mutated_object: None,
+    reject_request_with_mode(
         message,
         code,
         audit_annotations,
         warnings,
-    })?)
+        &PolicyMode::Protect,
+    )
+}
+
+/// Create a rejection response that honors the execution mode of the policy.
+/// When the policy runs in monitor mode the request is accepted, the
+/// rejection is reported via a warning and an audit annotation. See
+/// [`ValidationResponseBuilder::mode`].
+/// # Arguments
+/// * `message` -  message shown to the user
+/// * `code` -  code shown to the user
+/// * `audit_annotations` - see [`reject_request`]
+/// * `warnings` -  see [`reject_request`]
+/// * `mode` - the execution mode of the policy, usually taken from the policy settings
+pub fn reject_request_with_mode(
+    message: Option<String>,
+    code: Option<u16>,
+    audit_annotations: Option<HashMap<String, String>>,
+    warnings: Option<Vec<String>>,
+    mode: &PolicyMode,
+) -> wapc_guest::CallResult {
+    Ok(serde_json::to_vec(&apply_policy_mode(
+        ValidationResponse {
+            accepted: false,
+            mutated_object: None,
+            message,
+            code,
+            audit_annotations,
+            warnings,
+        },
+        mode,
+    ))?)
 }
 
 /// waPC guest function to register under the name `validate_settings`
@@ -207,28 +206,114 @@ pub fn validate_settings<T>(payload: &[u8]) -> wapc_guest::CallResult
 where
     T: serde::de::DeserializeOwned + settings::Validatable,
 {
-    let settings: T = serde_json::from_slice::<T>(payload).map_err(|e| {
-        anyhow!(
-            "Error decoding validation payload {}: {:?}",
-            String::from_utf8_lossy(payload),
-            e
-        )
-    })?;
+    let res: settings::SettingsValidationResponse = settings::load_settings::<T>(payload)
+        .and_then(|settings| settings.validate_fields())
+        .into();
 
-    let res = match settings.validate() {
-        Ok(_) => settings::SettingsValidationResponse {
-            valid: true,
-            message: None,
-        },
-        Err(e) => settings::SettingsValidationResponse {
-            valid: false,
-            message: Some(e),
-        },
-    };
+    Ok(serde_json::to_vec(&res)?)
+}
+
+/// Like [`validate_settings`], but once the local checks succeed the settings
+/// are also validated by [`settings::ContextAwareValidatable::validate_with_context`],
+/// which can consult the host capabilities. For example, to ensure a
+/// referenced ConfigMap exists.
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::settings::{
+///     ContextAwareValidatable, SettingsContext, SettingsErrors, Validatable,
+/// };
+/// use kubewarden_policy_sdk::validate_settings_with_context;
+/// use wapc_guest::register_function;
+///
+/// #[derive(serde::Deserialize)]
+/// struct Settings {
+///   image: String,
+/// }
+///
+/// impl Validatable for Settings {
+///   fn validate(&self) -> Result<(), String> {
+///     Ok(())
+///   }
+/// }
+///
+/// impl ContextAwareValidatable for Settings {
+///   fn validate_with_context(&self, context: &SettingsContext) -> Result<(), SettingsErrors> {
+///     let mut errors = SettingsErrors::new();
+///     if let Err(e) = context.get_manifest_digest(&self.image) {
+///       errors.add("image", &format!("cannot resolve image: {}", e));
+///     }
+///     errors.into_result()
+///   }
+/// }
+///
+/// register_function("validate_settings", validate_settings_with_context::<Settings>);
+/// ```
+pub fn validate_settings_with_context<T>(payload: &[u8]) -> wapc_guest::CallResult
+where
+    T: serde::de::DeserializeOwned + settings::ContextAwareValidatable,
+{
+    let res: settings::SettingsValidationResponse = settings::load_settings::<T>(payload)
+        .and_then(|settings| {
+            settings.validate_fields()?;
+            settings.validate_with_context(&settings::SettingsContext::new())
+        })
+        .into();
 
     Ok(serde_json::to_vec(&res)?)
 }
 
+/// Like [`validate_settings`], but the settings are first checked against the
+/// JSON Schema of the settings type. The schema errors report the path of the
+/// offending fields, `Validatable::validate` is invoked only when the settings
+/// conform to the schema.
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::{settings::Validatable, settings_schema_guest, validate_settings_with_schema};
+/// use wapc_guest::register_function;
+///
+/// #[derive(serde::Deserialize, schemars::JsonSchema)]
+/// struct Settings {
+///   allowed_registries: Vec<String>,
+/// }
+///
+/// impl Validatable for Settings {
+///   fn validate(&self) -> Result<(), String> {
+///     Ok(())
+///   }
+/// }
+///
+/// register_function("validate_settings", validate_settings_with_schema::<Settings>);
+/// register_function("settings_schema", settings_schema_guest::<Settings>);
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "settings-schema")))]
+#[cfg(feature = "settings-schema")]
+pub fn validate_settings_with_schema<T>(payload: &[u8]) -> wapc_guest::CallResult
+where
+    T: serde::de::DeserializeOwned + settings::Validatable + schemars::JsonSchema,
+{
+    let result = settings::deserialize_settings::<serde_json::Value>(payload)
+        .and_then(|settings| settings::validate_against_schema::<T>(&settings));
+    if let Err(errors) = result {
+        return Ok(serde_json::to_vec(
+            &settings::SettingsValidationResponse::invalid(errors),
+        )?);
+    }
+
+    validate_settings::<T>(payload)
+}
+
+/// waPC guest function returning the JSON Schema of the settings type `T`,
+/// meant to be registered under the name `settings_schema`. Tooling can use
+/// it to render settings forms, or to validate the settings of a policy
+/// before deploying it.
+#[cfg_attr(docsrs, doc(cfg(feature = "settings-schema")))]
+#[cfg(feature = "settings-schema")]
+pub fn settings_schema_guest<T: schemars::JsonSchema>(_payload: &[u8]) -> wapc_guest::CallResult {
+    Ok(serde_json::to_vec(&settings::settings_schema::<T>())?)
+}
+
 /// Helper function that provides the `protocol_version` implementation
 /// # Example
 ///
@@ -261,9 +346,10 @@ mod tests {
             use serde::Serialize;
             use serde::ser::StdError;
 
-            use k8s_openapi::api::batch::v1::{CronJob, CronJobSpec, JobSpec, JobTemplateSpec};
-            use k8s_openapi::api::core::v1::PodTemplateSpec;
-            use k8s_openapi::api::core::v1::{ReplicationController, ReplicationControllerSpec};
+            use k8s_openapi::api::batch::v1::{CronJob, CronJobSpec, Job, JobSpec, JobTemplateSpec};
+            use k8s_openapi::api::core::v1::{Pod, ReplicationController, ReplicationControllerSpec};
+            use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+            use std::collections::BTreeMap;
             use k8s_openapi::api::apps::v1::{
                 DaemonSet, DaemonSetSpec, Deployment, DeploymentSpec, ReplicaSet, ReplicaSetSpec,
                 StatefulSet, StatefulSetSpec,
@@ -304,6 +390,53 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_mutate_request_with_patch() -> Result<(), ()> {
+        let original_object = json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {
+                "name": "nginx"
+            }
+        });
+        let patch: patch::Patch = serde_json::from_value(json!([
+            { "op": "add", "path": "/metadata/labels", "value": { "app": "nginx" } }
+        ]))
+        .unwrap();
+
+        let reponse_raw = mutate_request_with_patch(&original_object, &patch).unwrap();
+        let response: ValidationResponse = serde_json::from_slice(&reponse_raw).unwrap();
+
+        assert!(response.accepted);
+        assert_json_eq!(
+            response.mutated_object,
+            json!({
+                "apiVersion": "v1",
+                "kind": "Pod",
+                "metadata": {
+                    "name": "nginx",
+                    "labels": {
+                        "app": "nginx"
+                    }
+                }
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mutate_request_with_invalid_patch() -> Result<(), ()> {
+        let original_object = json!({"metadata": {"name": "nginx"}});
+        let patch: patch::Patch = serde_json::from_value(json!([
+            { "op": "remove", "path": "/spec" }
+        ]))
+        .unwrap();
+
+        assert!(mutate_request_with_patch(&original_object, &patch).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_accept_request() -> Result<(), ()> {
         let reponse_raw = accept_request().unwrap();
@@ -348,6 +481,47 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_reject_request_in_monitor_mode() {
+        let response_raw = reject_request_with_mode(
+            Some("privileged containers are not allowed".to_string()),
+            Some(403),
+            None,
+            None,
+            &PolicyMode::Monitor,
+        )
+        .unwrap();
+        let response: ValidationResponse = serde_json::from_slice(&response_raw).unwrap();
+
+        assert!(response.accepted);
+        assert!(response.code.is_none());
+        assert!(response.message.is_none());
+        assert_eq!(
+            response.audit_annotations.unwrap()[MONITOR_MODE_AUDIT_ANNOTATION],
+            "privileged containers are not allowed"
+        );
+        assert_eq!(
+            response.warnings,
+            Some(vec![
+                "monitor mode: the request would have been rejected: privileged containers are not allowed".to_string()
+            ])
+        );
+
+        let response_raw =
+            reject_request_with_mode(None, None, None, None, &PolicyMode::Protect).unwrap();
+        let response: ValidationResponse = serde_json::from_slice(&response_raw).unwrap();
+        assert!(!response.accepted);
+    }
+
+    #[test]
+    fn test_reject_request_keeps_warnings() {
+        let warning = "a".repeat(WARNING_MAX_LENGTH + 1);
+        let response_raw = reject_request(None, None, None, Some(vec![warning.clone()])).unwrap();
+        let response: ValidationResponse = serde_json::from_slice(&response_raw).unwrap();
+
+        assert_eq!(response.warnings, Some(vec![warning]));
+    }
+
     #[test]
     fn try_protocol_version_guest() -> Result<(), ()> {
         let reponse = protocol_version_guest(&[0; 0]).unwrap();
@@ -650,6 +824,42 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "cluster-context")]
+    #[test]
+    fn test_mutate_pod_template_from_request_with_deployment() -> Result<(), ()> {
+        let deployment = Deployment {
+            spec: Some(DeploymentSpec::default()),
+            ..Default::default()
+        };
+        let validation_request = create_validation_request(deployment, "Deployment");
+
+        let new_pod_template = PodTemplateSpec {
+            metadata: Some(ObjectMeta {
+                annotations: Some(BTreeMap::from([(
+                    "example.com/owner".to_string(),
+                    "team-a".to_string(),
+                )])),
+                ..Default::default()
+            }),
+            spec: Some(PodSpec {
+                automount_service_account_token: Some(true),
+                ..Default::default()
+            }),
+        };
+
+        let raw_response = mutate_pod_template_from_request(validation_request, new_pod_template);
+        let response: ValidationResponse =
+            serde_json::from_slice(raw_response.as_ref().unwrap()).unwrap();
+        let annotation = jsonpath::select(
+            response.mutated_object.as_ref().unwrap(),
+            "$.spec.template.metadata.annotations['example.com/owner']",
+        )
+        .unwrap();
+        assert_eq!(annotation, vec!["team-a"], "Request not mutated");
+
+        check_if_automount_service_account_token_is_true(raw_response)
+    }
+
     #[cfg(feature = "cluster-context")]
     #[test]
     fn test_mutate_pod_spec_from_request_with_invalid_resource_type() -> Result<(), ()> {
@@ -676,4 +886,118 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(feature = "settings-schema")]
+    #[test]
+    fn test_validate_settings_with_schema() {
+        #[derive(serde::Deserialize, schemars::JsonSchema)]
+        struct Settings {
+            replicas: u32,
+        }
+
+        impl settings::Validatable for Settings {
+            fn validate(&self) -> Result<(), String> {
+                if self.replicas > 10 {
+                    return Err("too many replicas".to_string());
+                }
+                Ok(())
+            }
+        }
+
+        for (settings, valid, message) in [
+            (json!({"replicas": 3}), true, None),
+            (
+                json!({"replicas": 30}),
+                false,
+                Some("too many replicas".to_string()),
+            ),
+            (
+                json!({"replicas": "three"}),
+                false,
+                Some(r#"replicas: "three" is not of type "integer""#.to_string()),
+            ),
+        ] {
+            let response =
+                validate_settings_with_schema::<Settings>(&serde_json::to_vec(&settings).unwrap())
+                    .unwrap();
+            let response: settings::SettingsValidationResponse =
+                serde_json::from_slice(&response).unwrap();
+            assert_eq!(response.valid, valid);
+            assert_eq!(response.message, message);
+        }
+
+        let schema: serde_json::Value =
+            serde_json::from_slice(&settings_schema_guest::<Settings>(b"").unwrap()).unwrap();
+        assert_eq!(schema["properties"]["replicas"]["type"], "integer");
+    }
+
+    #[test]
+    fn test_validate_settings_with_context() {
+        use crate::host_capabilities::oci::ManifestDigestResponse;
+        use crate::host_capabilities::transport::{self, InMemoryTransport};
+        use settings::{ContextAwareValidatable, SettingsContext, SettingsErrors, Validatable};
+
+        #[derive(serde::Deserialize)]
+        struct Settings {
+            image: String,
+        }
+
+        impl Validatable for Settings {
+            fn validate(&self) -> Result<(), String> {
+                if self.image.is_empty() {
+                    return Err("image cannot be empty".to_string());
+                }
+                Ok(())
+            }
+        }
+
+        impl ContextAwareValidatable for Settings {
+            fn validate_with_context(
+                &self,
+                context: &SettingsContext,
+            ) -> Result<(), SettingsErrors> {
+                let mut errors = SettingsErrors::new();
+                if let Err(e) = context.get_manifest_digest(&self.image) {
+                    errors.add("image", &format!("cannot resolve image: {}", e));
+                }
+                errors.into_result()
+            }
+        }
+
+        let evaluate = |settings: serde_json::Value| -> settings::SettingsValidationResponse {
+            let response =
+                validate_settings_with_context::<Settings>(&serde_json::to_vec(&settings).unwrap())
+                    .unwrap();
+            serde_json::from_slice(&response).unwrap()
+        };
+
+        let transport = std::rc::Rc::new(InMemoryTransport::new().respond_with(
+            "oci",
+            "v1/manifest_digest",
+            &ManifestDigestResponse {
+                digest: "sha256:983".to_string(),
+            },
+        ));
+        {
+            let _guard = transport::install(transport.clone());
+            assert!(evaluate(json!({"image": "busybox:latest"})).valid);
+
+            // the host is not consulted when the local checks fail
+            let response = evaluate(json!({"image": ""}));
+            assert_eq!(response.message, Some("image cannot be empty".to_string()));
+            assert_eq!(transport.calls().len(), 1);
+        }
+
+        let _guard = transport::install(InMemoryTransport::new().fail_with(
+            "oci",
+            "v1/manifest_digest",
+            "registry unreachable",
+        ));
+        let response = evaluate(json!({"image": "busybox:latest"}));
+        assert!(!response.valid);
+        assert!(response
+            .message
+            .unwrap()
+            .starts_with("image: cannot resolve image:"));
+    }
 }
@@ -7,21 +7,18 @@ use std::{convert::TryFrom, fmt};
 ///
 /// Policies built with this SDK provide the right value via the `protocol_version_guest`
 /// function.
-#[derive(Deserialize, Serialize, Debug, Clone, FromPrimitive, ToPrimitive, PartialEq, Eq)]
+#[derive(
+    Deserialize, Serialize, Debug, Clone, Default, FromPrimitive, ToPrimitive, PartialEq, Eq,
+)]
 pub enum ProtocolVersion {
     /// This is an invalid version
     #[serde(rename = "Unknown")]
     Unknown = 0,
     #[serde(rename = "v1")]
+    #[default]
     V1,
 }
 
-impl Default for ProtocolVersion {
-    fn default() -> Self {
-        Self::V1
-    }
-}
-
 impl TryFrom<Vec<u8>> for ProtocolVersion {
     type Error = anyhow::Error;
 
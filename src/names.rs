@@ -0,0 +1,142 @@
+// Validation of the names used by Kubernetes for label and annotation keys,
+// and for label values.
+// See https://kubernetes.io/docs/concepts/overview/working-with-objects/names/
+
+const QUALIFIED_NAME_MAX_LENGTH: usize = 63;
+#[cfg(feature = "cluster-context")]
+const LABEL_VALUE_MAX_LENGTH: usize = 63;
+const DNS1123_SUBDOMAIN_MAX_LENGTH: usize = 253;
+
+/// Ensure `key` is a valid qualified name: an optional DNS subdomain prefix
+/// followed by a slash, and a name made of at most 63 alphanumeric characters,
+/// `-`, `_` or `.`, beginning and ending with an alphanumeric character.
+/// Label and annotation keys must be qualified names.
+pub(crate) fn validate_qualified_name(key: &str) -> Result<(), String> {
+    let (prefix, name) = match key.split_once('/') {
+        Some((prefix, name)) => (Some(prefix), name),
+        None => (None, key),
+    };
+
+    if let Some(prefix) = prefix {
+        validate_dns1123_subdomain(prefix)
+            .map_err(|e| format!("invalid prefix of '{}': {}", key, e))?;
+    }
+
+    if name.is_empty() {
+        return Err(format!(
+            "invalid name '{}': name part must be non-empty",
+            key
+        ));
+    }
+    if name.len() > QUALIFIED_NAME_MAX_LENGTH {
+        return Err(format!(
+            "invalid name '{}': name part must be no more than {} characters",
+            key, QUALIFIED_NAME_MAX_LENGTH
+        ));
+    }
+    if !is_alphanumeric_with_separators(name, &['-', '_', '.']) {
+        return Err(format!(
+            "invalid name '{}': name part must consist of alphanumeric characters, '-', '_' or '.', and must start and end with an alphanumeric character",
+            key
+        ));
+    }
+    Ok(())
+}
+
+/// Ensure `value` is a valid label value: either empty, or made of at most 63
+/// alphanumeric characters, `-`, `_` or `.`, beginning and ending with an
+/// alphanumeric character.
+#[cfg(feature = "cluster-context")]
+pub(crate) fn validate_label_value(value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Ok(());
+    }
+    if value.len() > LABEL_VALUE_MAX_LENGTH {
+        return Err(format!(
+            "invalid label value '{}': must be no more than {} characters",
+            value, LABEL_VALUE_MAX_LENGTH
+        ));
+    }
+    if !is_alphanumeric_with_separators(value, &['-', '_', '.']) {
+        return Err(format!(
+            "invalid label value '{}': must consist of alphanumeric characters, '-', '_' or '.', and must start and end with an alphanumeric character",
+            value
+        ));
+    }
+    Ok(())
+}
+
+/// Ensure `value` is a DNS subdomain as defined by RFC 1123: lowercase
+/// alphanumeric characters, `-` or `.`, beginning and ending with an
+/// alphanumeric character.
+fn validate_dns1123_subdomain(value: &str) -> Result<(), String> {
+    if value.is_empty() || value.len() > DNS1123_SUBDOMAIN_MAX_LENGTH {
+        return Err(format!(
+            "must be between 1 and {} characters",
+            DNS1123_SUBDOMAIN_MAX_LENGTH
+        ));
+    }
+    if value.chars().any(|c| c.is_ascii_uppercase())
+        || !is_alphanumeric_with_separators(value, &['-', '.'])
+    {
+        return Err("must consist of lower case alphanumeric characters, '-' or '.', and must start and end with an alphanumeric character".to_string());
+    }
+    Ok(())
+}
+
+fn is_alphanumeric_with_separators(value: &str, separators: &[char]) -> bool {
+    let starts_and_ends_with_alphanumeric = value
+        .chars()
+        .next()
+        .zip(value.chars().last())
+        .is_some_and(|(first, last)| first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric());
+
+    starts_and_ends_with_alphanumeric
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || separators.contains(&c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qualified_names() {
+        for valid in [
+            "app",
+            "app.kubernetes.io/name",
+            "imagepolicy.example.com/error",
+            "a_b-c.d",
+            "A1",
+        ] {
+            assert!(validate_qualified_name(valid).is_ok(), "{}", valid);
+        }
+
+        for invalid in [
+            "",
+            "/name",
+            "Example.com/name",
+            "example.com/",
+            "-app",
+            "app_",
+            "a/b/c",
+            "with space",
+            &"a".repeat(64),
+        ] {
+            assert!(validate_qualified_name(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[cfg(feature = "cluster-context")]
+    #[test]
+    fn label_values() {
+        for valid in ["", "nginx", "v1.2.3", "a_b-c", &"a".repeat(63)] {
+            assert!(validate_label_value(valid).is_ok(), "{}", valid);
+        }
+
+        for invalid in ["-nginx", "nginx.", "a/b", "with space", &"a".repeat(64)] {
+            assert!(validate_label_value(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
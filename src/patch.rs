@@ -0,0 +1,125 @@
+use anyhow::{anyhow, Result};
+
+pub use json_patch::{Patch, PatchOperation};
+
+/// Compute the JSON Patch (RFC 6902) that turns `original_object` into
+/// `mutated_object`.
+///
+/// The returned patch can be logged to review the changes made by a mutating
+/// policy, or given to [`crate::mutate_request_with_patch`].
+/// # Arguments
+/// * `original_object` - the object from the incoming request
+/// * `mutated_object` - the object as it should look after the mutation
+pub fn diff(original_object: &serde_json::Value, mutated_object: &serde_json::Value) -> Patch {
+    json_patch::diff(original_object, mutated_object)
+}
+
+/// Apply a JSON Patch (RFC 6902) to `original_object` and return the
+/// resulting object. The original object is left untouched.
+///
+/// The patch is applied atomically: an error is returned when one of the
+/// operations cannot be applied (e.g. a `test` operation fails or a path does
+/// not exist).
+pub fn apply(original_object: &serde_json::Value, patch: &Patch) -> Result<serde_json::Value> {
+    let mut mutated_object = original_object.clone();
+    json_patch::patch(&mut mutated_object, patch)
+        .map_err(|e| anyhow!("error applying JSON patch: {}", e))?;
+    Ok(mutated_object)
+}
+
+/// Ensure that applying `patch` to `original_object` produces exactly
+/// `expected_object`.
+pub fn verify(
+    original_object: &serde_json::Value,
+    patch: &Patch,
+    expected_object: &serde_json::Value,
+) -> Result<()> {
+    let mutated_object = apply(original_object, patch)?;
+    if &mutated_object != expected_object {
+        return Err(anyhow!(
+            "JSON patch does not produce the expected object, remaining differences: {}",
+            diff(&mutated_object, expected_object)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pod() -> serde_json::Value {
+        json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {
+                "name": "nginx",
+                "labels": {
+                    "app": "nginx"
+                }
+            },
+            "spec": {
+                "containers": [
+                    {
+                        "name": "nginx",
+                        "image": "nginx:latest"
+                    }
+                ]
+            }
+        })
+    }
+
+    #[test]
+    fn diff_and_apply_roundtrip() {
+        let original = pod();
+        let mut mutated = pod();
+        mutated["metadata"]["labels"]["owner"] = json!("team-a");
+        mutated["spec"]["containers"][0]["image"] = json!("nginx:1.27");
+
+        let patch = diff(&original, &mutated);
+        assert_eq!(patch.len(), 2);
+
+        let patched = apply(&original, &patch).expect("cannot apply patch");
+        assert_eq!(patched, mutated);
+        assert!(verify(&original, &patch, &mutated).is_ok());
+    }
+
+    #[test]
+    fn diff_of_identical_objects_is_empty() {
+        let patch = diff(&pod(), &pod());
+        assert!(patch.is_empty());
+    }
+
+    #[test]
+    fn apply_hand_built_patch() {
+        let patch: Patch = serde_json::from_value(json!([
+            { "op": "test", "path": "/metadata/name", "value": "nginx" },
+            { "op": "remove", "path": "/metadata/labels/app" }
+        ]))
+        .unwrap();
+
+        let patched = apply(&pod(), &patch).expect("cannot apply patch");
+        assert_eq!(patched["metadata"]["labels"], json!({}));
+    }
+
+    #[test]
+    fn apply_invalid_patch() {
+        let patch: Patch = serde_json::from_value(json!([
+            { "op": "test", "path": "/metadata/name", "value": "busybox" },
+        ]))
+        .unwrap();
+
+        assert!(apply(&pod(), &patch).is_err());
+    }
+
+    #[test]
+    fn verify_detects_unexpected_result() {
+        let original = pod();
+        let mut expected = pod();
+        expected["metadata"]["labels"]["owner"] = json!("team-a");
+
+        let patch = diff(&original, &pod());
+        assert!(verify(&original, &patch, &expected).is_err());
+    }
+}
@@ -0,0 +1,415 @@
+use anyhow::anyhow;
+use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet};
+use k8s_openapi::api::batch::v1::{CronJob, Job};
+use k8s_openapi::api::core::v1::{Pod, PodSpec, PodTemplateSpec, ReplicationController};
+use k8s_openapi::Resource;
+
+/// Error message returned when dealing with an object that doesn't have a pod template
+pub(crate) const UNSUPPORTED_KIND_ERROR: &str = "Object should be one of these kinds: Deployment, ReplicaSet, StatefulSet, DaemonSet, ReplicationController, Job, CronJob, Pod";
+
+/// Read and write the pod template of the high level objects creating Pods.
+///
+/// This trait is implemented by all the objects supported by the SDK:
+/// Deployment, ReplicaSet, StatefulSet, DaemonSet, ReplicationController,
+/// Job, CronJob and Pod. For Pods, the template is made of the metadata and
+/// the spec of the Pod itself.
+pub trait PodTemplateAccessor {
+    /// Return the pod template, including its metadata, if present
+    fn pod_template(&self) -> Option<PodTemplateSpec>;
+
+    /// Replace the pod template, creating the intermediate objects when missing
+    fn set_pod_template(&mut self, pod_template: PodTemplateSpec);
+
+    /// Return the PodSpec of the pod template, if present
+    fn pod_spec(&self) -> Option<PodSpec> {
+        self.pod_template().and_then(|template| template.spec)
+    }
+
+    /// Replace the PodSpec of the pod template, leaving its metadata untouched
+    fn set_pod_spec(&mut self, pod_spec: PodSpec) {
+        let mut template = self.pod_template().unwrap_or_default();
+        template.spec = Some(pod_spec);
+        self.set_pod_template(template);
+    }
+}
+
+impl PodTemplateAccessor for Deployment {
+    fn pod_template(&self) -> Option<PodTemplateSpec> {
+        self.spec.as_ref().map(|spec| spec.template.clone())
+    }
+
+    fn set_pod_template(&mut self, pod_template: PodTemplateSpec) {
+        self.spec.get_or_insert_with(Default::default).template = pod_template;
+    }
+}
+
+impl PodTemplateAccessor for ReplicaSet {
+    fn pod_template(&self) -> Option<PodTemplateSpec> {
+        self.spec.as_ref().and_then(|spec| spec.template.clone())
+    }
+
+    fn set_pod_template(&mut self, pod_template: PodTemplateSpec) {
+        self.spec.get_or_insert_with(Default::default).template = Some(pod_template);
+    }
+}
+
+impl PodTemplateAccessor for StatefulSet {
+    fn pod_template(&self) -> Option<PodTemplateSpec> {
+        self.spec.as_ref().map(|spec| spec.template.clone())
+    }
+
+    fn set_pod_template(&mut self, pod_template: PodTemplateSpec) {
+        self.spec.get_or_insert_with(Default::default).template = pod_template;
+    }
+}
+
+impl PodTemplateAccessor for DaemonSet {
+    fn pod_template(&self) -> Option<PodTemplateSpec> {
+        self.spec.as_ref().map(|spec| spec.template.clone())
+    }
+
+    fn set_pod_template(&mut self, pod_template: PodTemplateSpec) {
+        self.spec.get_or_insert_with(Default::default).template = pod_template;
+    }
+}
+
+impl PodTemplateAccessor for ReplicationController {
+    fn pod_template(&self) -> Option<PodTemplateSpec> {
+        self.spec.as_ref().and_then(|spec| spec.template.clone())
+    }
+
+    fn set_pod_template(&mut self, pod_template: PodTemplateSpec) {
+        self.spec.get_or_insert_with(Default::default).template = Some(pod_template);
+    }
+}
+
+impl PodTemplateAccessor for CronJob {
+    fn pod_template(&self) -> Option<PodTemplateSpec> {
+        self.spec
+            .as_ref()
+            .and_then(|spec| spec.job_template.spec.as_ref())
+            .map(|job_spec| job_spec.template.clone())
+    }
+
+    fn set_pod_template(&mut self, pod_template: PodTemplateSpec) {
+        self.spec
+            .get_or_insert_with(Default::default)
+            .job_template
+            .spec
+            .get_or_insert_with(Default::default)
+            .template = pod_template;
+    }
+}
+
+impl PodTemplateAccessor for Job {
+    fn pod_template(&self) -> Option<PodTemplateSpec> {
+        self.spec.as_ref().map(|spec| spec.template.clone())
+    }
+
+    fn set_pod_template(&mut self, pod_template: PodTemplateSpec) {
+        self.spec.get_or_insert_with(Default::default).template = pod_template;
+    }
+}
+
+impl PodTemplateAccessor for Pod {
+    fn pod_template(&self) -> Option<PodTemplateSpec> {
+        Some(PodTemplateSpec {
+            metadata: Some(self.metadata.clone()),
+            spec: self.spec.clone(),
+        })
+    }
+
+    /// Only the labels and the annotations of the template are copied into
+    /// the metadata of the Pod, the other fields, like the name, the
+    /// namespace, the UID and the owner references, are left untouched
+    fn set_pod_template(&mut self, pod_template: PodTemplateSpec) {
+        let metadata = pod_template.metadata.unwrap_or_default();
+        self.metadata.labels = metadata.labels;
+        self.metadata.annotations = metadata.annotations;
+        self.spec = pod_template.spec;
+    }
+}
+
+/// Returns the JSON path of the PodSpec inside of objects of the given kind,
+/// `None` if the kind is not supported
+pub fn pod_spec_path(kind: &str) -> Option<&'static str> {
+    match kind {
+        Deployment::KIND
+        | ReplicaSet::KIND
+        | StatefulSet::KIND
+        | DaemonSet::KIND
+        | ReplicationController::KIND
+        | Job::KIND => Some("spec.template.spec"),
+        CronJob::KIND => Some("spec.jobTemplate.spec.template.spec"),
+        Pod::KIND => Some("spec"),
+        _ => None,
+    }
+}
+
+/// One of the high level objects that create Pods
+#[derive(Debug, Clone, PartialEq)]
+pub enum Workload {
+    // Using box here to make linter happy. It complains about the different sizes between
+    // the enum elements. See more here:
+    // https://rust-lang.github.io/rust-clippy/master/index.html#/large_enum_variant
+    Deployment(Box<Deployment>),
+    ReplicaSet(Box<ReplicaSet>),
+    StatefulSet(Box<StatefulSet>),
+    DaemonSet(Box<DaemonSet>),
+    ReplicationController(Box<ReplicationController>),
+    CronJob(Box<CronJob>),
+    Job(Box<Job>),
+    Pod(Box<Pod>),
+}
+
+impl Workload {
+    /// Parse the given object according to its kind.
+    /// Returns `None` when the kind is not one of the supported workloads,
+    /// and an error when the object cannot be parsed.
+    pub fn from_object(kind: &str, object: &serde_json::Value) -> anyhow::Result<Option<Self>> {
+        let workload = match kind {
+            Deployment::KIND => {
+                Workload::Deployment(Box::new(serde_json::from_value(object.clone())?))
+            }
+            ReplicaSet::KIND => {
+                Workload::ReplicaSet(Box::new(serde_json::from_value(object.clone())?))
+            }
+            StatefulSet::KIND => {
+                Workload::StatefulSet(Box::new(serde_json::from_value(object.clone())?))
+            }
+            DaemonSet::KIND => {
+                Workload::DaemonSet(Box::new(serde_json::from_value(object.clone())?))
+            }
+            ReplicationController::KIND => {
+                Workload::ReplicationController(Box::new(serde_json::from_value(object.clone())?))
+            }
+            CronJob::KIND => Workload::CronJob(Box::new(serde_json::from_value(object.clone())?)),
+            Job::KIND => Workload::Job(Box::new(serde_json::from_value(object.clone())?)),
+            Pod::KIND => Workload::Pod(Box::new(serde_json::from_value(object.clone())?)),
+            _ => return Ok(None),
+        };
+        Ok(Some(workload))
+    }
+
+    /// Like [`Workload::from_object`], but returns an error when the kind is
+    /// not supported
+    pub fn try_from_object(kind: &str, object: &serde_json::Value) -> anyhow::Result<Self> {
+        Workload::from_object(kind, object)?.ok_or_else(|| anyhow!(UNSUPPORTED_KIND_ERROR))
+    }
+
+    /// Returns the JSON path of the PodSpec inside of the workload
+    pub fn pod_spec_path(&self) -> &'static str {
+        match self {
+            Workload::CronJob(_) => "spec.jobTemplate.spec.template.spec",
+            Workload::Pod(_) => "spec",
+            _ => "spec.template.spec",
+        }
+    }
+
+    /// Serialize the workload back into a JSON object
+    pub fn to_value(&self) -> serde_json::Result<serde_json::Value> {
+        match self {
+            Workload::Deployment(o) => serde_json::to_value(o),
+            Workload::ReplicaSet(o) => serde_json::to_value(o),
+            Workload::StatefulSet(o) => serde_json::to_value(o),
+            Workload::DaemonSet(o) => serde_json::to_value(o),
+            Workload::ReplicationController(o) => serde_json::to_value(o),
+            Workload::CronJob(o) => serde_json::to_value(o),
+            Workload::Job(o) => serde_json::to_value(o),
+            Workload::Pod(o) => serde_json::to_value(o),
+        }
+    }
+
+    fn accessor(&self) -> &dyn PodTemplateAccessor {
+        match self {
+            Workload::Deployment(o) => o.as_ref(),
+            Workload::ReplicaSet(o) => o.as_ref(),
+            Workload::StatefulSet(o) => o.as_ref(),
+            Workload::DaemonSet(o) => o.as_ref(),
+            Workload::ReplicationController(o) => o.as_ref(),
+            Workload::CronJob(o) => o.as_ref(),
+            Workload::Job(o) => o.as_ref(),
+            Workload::Pod(o) => o.as_ref(),
+        }
+    }
+
+    fn accessor_mut(&mut self) -> &mut dyn PodTemplateAccessor {
+        match self {
+            Workload::Deployment(o) => o.as_mut(),
+            Workload::ReplicaSet(o) => o.as_mut(),
+            Workload::StatefulSet(o) => o.as_mut(),
+            Workload::DaemonSet(o) => o.as_mut(),
+            Workload::ReplicationController(o) => o.as_mut(),
+            Workload::CronJob(o) => o.as_mut(),
+            Workload::Job(o) => o.as_mut(),
+            Workload::Pod(o) => o.as_mut(),
+        }
+    }
+}
+
+impl PodTemplateAccessor for Workload {
+    fn pod_template(&self) -> Option<PodTemplateSpec> {
+        self.accessor().pod_template()
+    }
+
+    fn set_pod_template(&mut self, pod_template: PodTemplateSpec) {
+        self.accessor_mut().set_pod_template(pod_template)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::batch::v1::{CronJobSpec, JobSpec, JobTemplateSpec};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ObjectMeta, OwnerReference};
+    use std::collections::BTreeMap;
+
+    fn annotated_template() -> PodTemplateSpec {
+        PodTemplateSpec {
+            metadata: Some(ObjectMeta {
+                annotations: Some(BTreeMap::from([(
+                    "example.com/owner".to_string(),
+                    "team-a".to_string(),
+                )])),
+                ..Default::default()
+            }),
+            spec: Some(PodSpec {
+                hostname: Some("nginx".to_string()),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn set_pod_template_creates_missing_specs() {
+        let mut cronjob = CronJob::default();
+        assert!(cronjob.pod_template().is_none());
+
+        cronjob.set_pod_template(annotated_template());
+        assert_eq!(cronjob.pod_template(), Some(annotated_template()));
+        assert_eq!(
+            cronjob
+                .spec
+                .unwrap()
+                .job_template
+                .spec
+                .unwrap()
+                .template
+                .metadata
+                .unwrap()
+                .annotations
+                .unwrap()["example.com/owner"],
+            "team-a"
+        );
+    }
+
+    #[test]
+    fn set_pod_spec_keeps_template_metadata() {
+        let mut cronjob = CronJob {
+            spec: Some(CronJobSpec {
+                job_template: JobTemplateSpec {
+                    spec: Some(JobSpec {
+                        template: annotated_template(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let pod_spec = PodSpec {
+            hostname: Some("busybox".to_string()),
+            ..Default::default()
+        };
+
+        cronjob.set_pod_spec(pod_spec.clone());
+        let template = cronjob.pod_template().unwrap();
+        assert_eq!(template.metadata, annotated_template().metadata);
+        assert_eq!(template.spec, Some(pod_spec));
+    }
+
+    #[test]
+    fn pod_template_of_pod() {
+        let mut pod = Pod::default();
+        pod.set_pod_template(annotated_template());
+
+        assert_eq!(pod.metadata, annotated_template().metadata.unwrap());
+        assert_eq!(pod.spec, annotated_template().spec);
+        assert_eq!(pod.pod_template(), Some(annotated_template()));
+    }
+
+    #[test]
+    fn set_pod_template_keeps_pod_identity() {
+        let metadata = ObjectMeta {
+            name: Some("nginx".to_string()),
+            namespace: Some("default".to_string()),
+            uid: Some("c0ffee".to_string()),
+            owner_references: Some(vec![OwnerReference {
+                api_version: "apps/v1".to_string(),
+                kind: "ReplicaSet".to_string(),
+                name: "nginx-5d4f8".to_string(),
+                uid: "bad".to_string(),
+                ..Default::default()
+            }]),
+            labels: Some(BTreeMap::from([("app".to_string(), "nginx".to_string())])),
+            ..Default::default()
+        };
+        let mut pod = Pod {
+            metadata: metadata.clone(),
+            ..Default::default()
+        };
+
+        pod.set_pod_template(annotated_template());
+        assert_eq!(
+            pod.metadata,
+            ObjectMeta {
+                labels: None,
+                annotations: annotated_template().metadata.unwrap().annotations,
+                ..metadata
+            }
+        );
+        assert_eq!(pod.spec, annotated_template().spec);
+    }
+
+    #[test]
+    fn workload_roundtrip() {
+        let mut deployment = Deployment::default();
+        deployment.set_pod_template(annotated_template());
+        let object = serde_json::to_value(&deployment).unwrap();
+
+        let mut workload = Workload::try_from_object("Deployment", &object).unwrap();
+        assert_eq!(workload.pod_template(), Some(annotated_template()));
+
+        workload.set_pod_spec(PodSpec::default());
+        assert_eq!(
+            workload.to_value().unwrap()["spec"]["template"]["metadata"]["annotations"],
+            serde_json::json!({"example.com/owner": "team-a"})
+        );
+    }
+
+    #[test]
+    fn pod_spec_paths() {
+        assert_eq!(pod_spec_path("Deployment"), Some("spec.template.spec"));
+        assert_eq!(
+            pod_spec_path("CronJob"),
+            Some("spec.jobTemplate.spec.template.spec")
+        );
+        assert_eq!(pod_spec_path("Pod"), Some("spec"));
+        assert_eq!(pod_spec_path("ConfigMap"), None);
+
+        let workload = Workload::try_from_object("CronJob", &serde_json::json!({})).unwrap();
+        assert_eq!(workload.pod_spec_path(), pod_spec_path("CronJob").unwrap());
+    }
+
+    #[test]
+    fn workload_unsupported_kind() {
+        let object = serde_json::json!({});
+        assert!(Workload::from_object("ConfigMap", &object)
+            .unwrap()
+            .is_none());
+
+        let err = Workload::try_from_object("ConfigMap", &object).unwrap_err();
+        assert_eq!(err.to_string(), UNSUPPORTED_KIND_ERROR);
+    }
+}
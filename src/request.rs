@@ -1,12 +1,16 @@
 use anyhow::anyhow;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::settings::Validatable;
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "cluster-context")] {
-        use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet};
-        use k8s_openapi::api::batch::v1::{CronJob, Job};
-        use k8s_openapi::api::core::v1::{Pod, PodSpec, ReplicationController};
+        use crate::pod_template::{PodTemplateAccessor, Workload};
+        use k8s_openapi::api::core::v1::{PodSpec, PodTemplateSpec};
+        use k8s_openapi::apimachinery::pkg::apis::meta::v1::DeleteOptions;
         use k8s_openapi::Resource;
     }
 }
@@ -21,6 +25,22 @@ pub struct ValidationRequest<T: Default> {
     pub request: KubernetesAdmissionRequest,
 }
 
+/// The execution mode of a policy.
+///
+/// The host does not send the mode to the policy. Policies that support
+/// monitor mode read it from their own settings and give it to
+/// [`crate::response::ValidationResponseBuilder::mode`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "crd", derive(schemars::JsonSchema))]
+pub enum PolicyMode {
+    /// Requests violating the policy are rejected
+    #[default]
+    Protect,
+    /// Requests violating the policy are accepted, the rejections are
+    /// reported via warnings and audit annotations
+    Monitor,
+}
+
 /// Kubernetes' [AdmissionReview](https://kubernetes.io/docs/reference/access-authn-authz/extensible-admission-controllers/)
 /// request.
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -66,7 +86,7 @@ pub struct KubernetesAdmissionRequest {
     ///
     /// See documentation for the "matchPolicy" field in the webhook configuration type.
     #[serde(alias = "requestResource")]
-    pub request_resource: GroupVersionKind,
+    pub request_resource: GroupVersionResource,
 
     /// RequestSubResource is the name of the subresource of the original API request, if any (for example, "status" or "scale")
     /// If this is specified and differs from the value in "subResource", an equivalent match and conversion was performed.
@@ -83,7 +103,7 @@ pub struct KubernetesAdmissionRequest {
 
     /// Operation is the operation being performed. This may be different than the operation
     /// requested. e.g. a patch can result in either a CREATE or UPDATE Operation.
-    pub operation: String,
+    pub operation: Operation,
 
     /// UserInfo is information about the requesting user
     #[serde(alias = "userInfo")]
@@ -109,8 +129,170 @@ pub struct KubernetesAdmissionRequest {
     pub options: HashMap<String, serde_json::Value>,
 }
 
-/// GroupVersionKind unambiguously identifies a kind
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+/// Operation being performed by an admission request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+pub enum Operation {
+    Create,
+    Update,
+    Delete,
+    Connect,
+    /// Operation not known by this version of the SDK, holds the
+    /// value found inside of the request
+    Unknown(String),
+}
+
+impl Default for Operation {
+    fn default() -> Self {
+        Operation::Unknown(String::new())
+    }
+}
+
+impl Operation {
+    /// Returns the operation as it's written inside of the admission request
+    pub fn as_str(&self) -> &str {
+        match self {
+            Operation::Create => "CREATE",
+            Operation::Update => "UPDATE",
+            Operation::Delete => "DELETE",
+            Operation::Connect => "CONNECT",
+            Operation::Unknown(operation) => operation.as_str(),
+        }
+    }
+}
+
+impl From<String> for Operation {
+    fn from(operation: String) -> Self {
+        match operation.as_str() {
+            "CREATE" => Operation::Create,
+            "UPDATE" => Operation::Update,
+            "DELETE" => Operation::Delete,
+            "CONNECT" => Operation::Connect,
+            _ => Operation::Unknown(operation),
+        }
+    }
+}
+
+impl From<&str> for Operation {
+    fn from(operation: &str) -> Self {
+        Operation::from(operation.to_string())
+    }
+}
+
+impl From<Operation> for String {
+    fn from(operation: Operation) -> Self {
+        operation.as_str().to_string()
+    }
+}
+
+/// Compare with the operation as it's written inside of the admission
+/// request, e.g. `request.operation == "CREATE"`
+impl PartialEq<str> for Operation {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Operation {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Operation {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// CreateOptions may be provided when creating an API object.
+/// This is the `meta.k8s.io/v1.CreateOptions` type.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateOptions {
+    pub api_version: Option<String>,
+    pub kind: Option<String>,
+    /// When present, indicates that modifications should not be persisted.
+    pub dry_run: Option<Vec<String>>,
+    /// Name associated with the actor or entity that is making these changes.
+    pub field_manager: Option<String>,
+    /// Instructs the server on how to handle objects in the request containing
+    /// unknown or duplicate fields.
+    pub field_validation: Option<String>,
+}
+
+/// UpdateOptions may be provided when updating an API object.
+/// This is the `meta.k8s.io/v1.UpdateOptions` type.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateOptions {
+    pub api_version: Option<String>,
+    pub kind: Option<String>,
+    /// When present, indicates that modifications should not be persisted.
+    pub dry_run: Option<Vec<String>>,
+    /// Name associated with the actor or entity that is making these changes.
+    pub field_manager: Option<String>,
+    /// Instructs the server on how to handle objects in the request containing
+    /// unknown or duplicate fields.
+    pub field_validation: Option<String>,
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+#[cfg(feature = "cluster-context")]
+/// Typed version of the `options` of an admission request
+#[derive(Debug, Clone, PartialEq)]
+pub enum OperationOptions {
+    Create(CreateOptions),
+    Update(UpdateOptions),
+    Delete(DeleteOptions),
+}
+
+impl KubernetesAdmissionRequest {
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+    #[cfg(feature = "cluster-context")]
+    /// Decode the `options` of the request into the type matching the
+    /// operation being performed.
+    /// Returns `None` when no options are provided, or when the operation
+    /// is not one of CREATE, UPDATE and DELETE.
+    pub fn operation_options(&self) -> anyhow::Result<Option<OperationOptions>> {
+        if self.options.is_empty() {
+            return Ok(None);
+        }
+        let options = serde_json::Value::Object(
+            self.options
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        );
+        let decode_error =
+            |e: serde_json::Error| anyhow!("cannot decode {} options: {}", self.operation, e);
+
+        match self.operation {
+            Operation::Create => serde_json::from_value(options)
+                .map(|o| Some(OperationOptions::Create(o)))
+                .map_err(decode_error),
+            Operation::Update => serde_json::from_value(options)
+                .map(|o| Some(OperationOptions::Update(o)))
+                .map_err(decode_error),
+            Operation::Delete => serde_json::from_value(options)
+                .map(|o| Some(OperationOptions::Delete(o)))
+                .map_err(decode_error),
+            Operation::Connect | Operation::Unknown(_) => Ok(None),
+        }
+    }
+}
+
+/// GroupVersionKind unambiguously identifies a kind.
+///
+/// Its string representation is `group/version/kind`, or `version/kind` for
+/// the resources of the core group (e.g. `apps/v1/Deployment`, `v1/Pod`).
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, Hash)]
 #[serde(default)]
 pub struct GroupVersionKind {
     pub group: String,
@@ -118,13 +300,137 @@ pub struct GroupVersionKind {
     pub kind: String,
 }
 
-/// GroupVersionResource unambiguously identifies a resource
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+impl GroupVersionKind {
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+    #[cfg(feature = "cluster-context")]
+    /// Returns the GroupVersionKind of the Kubernetes type `K`
+    pub fn of<K: Resource>() -> Self {
+        GroupVersionKind {
+            group: K::GROUP.to_string(),
+            version: K::VERSION.to_string(),
+            kind: K::KIND.to_string(),
+        }
+    }
+
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+    #[cfg(feature = "cluster-context")]
+    /// Returns true if this is the GroupVersionKind of the Kubernetes type `K`
+    pub fn is<K: Resource>(&self) -> bool {
+        self.group == K::GROUP && self.version == K::VERSION && self.kind == K::KIND
+    }
+
+    /// Returns the `apiVersion` of the kind: `group/version`, or just `version`
+    /// for the core group
+    pub fn api_version(&self) -> String {
+        api_version(&self.group, &self.version)
+    }
+
+    /// Returns the `apiVersion` and `kind` pair, as used by the requests of
+    /// the kubernetes host capabilities (e.g. `GetResourceRequest`)
+    pub fn to_api_version_and_kind(&self) -> (String, String) {
+        (self.api_version(), self.kind.clone())
+    }
+}
+
+impl fmt::Display for GroupVersionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.api_version(), self.kind)
+    }
+}
+
+impl FromStr for GroupVersionKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (group, version, kind) = split_group_version_name(s)
+            .ok_or_else(|| anyhow!("invalid GroupVersionKind '{}'", s))?;
+        Ok(GroupVersionKind {
+            group,
+            version,
+            kind,
+        })
+    }
+}
+
+/// GroupVersionResource unambiguously identifies a resource.
+///
+/// Its string representation is `group/version/resource`, or `version/resource`
+/// for the resources of the core group (e.g. `apps/v1/deployments`, `v1/pods`).
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, Hash)]
 #[serde(default)]
 pub struct GroupVersionResource {
     pub group: String,
     pub version: String,
-    pub kind: String,
+    pub resource: String,
+}
+
+impl GroupVersionResource {
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+    #[cfg(feature = "cluster-context")]
+    /// Returns the GroupVersionResource of the Kubernetes type `K`
+    pub fn of<K: Resource>() -> Self {
+        GroupVersionResource {
+            group: K::GROUP.to_string(),
+            version: K::VERSION.to_string(),
+            resource: K::URL_PATH_SEGMENT.to_string(),
+        }
+    }
+
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+    #[cfg(feature = "cluster-context")]
+    /// Returns true if this is the GroupVersionResource of the Kubernetes type `K`
+    pub fn is<K: Resource>(&self) -> bool {
+        self.group == K::GROUP && self.version == K::VERSION && self.resource == K::URL_PATH_SEGMENT
+    }
+
+    /// Returns the `apiVersion` of the resource: `group/version`, or just
+    /// `version` for the core group
+    pub fn api_version(&self) -> String {
+        api_version(&self.group, &self.version)
+    }
+}
+
+impl fmt::Display for GroupVersionResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.api_version(), self.resource)
+    }
+}
+
+impl FromStr for GroupVersionResource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (group, version, resource) = split_group_version_name(s)
+            .ok_or_else(|| anyhow!("invalid GroupVersionResource '{}'", s))?;
+        Ok(GroupVersionResource {
+            group,
+            version,
+            resource,
+        })
+    }
+}
+
+fn api_version(group: &str, version: &str) -> String {
+    if group.is_empty() {
+        version.to_string()
+    } else {
+        format!("{}/{}", group, version)
+    }
+}
+
+/// Split strings like `group/version/name` and `version/name`. The latter is
+/// used by the core group, whose group is empty. The name is never empty.
+fn split_group_version_name(s: &str) -> Option<(String, String, String)> {
+    let parts: Vec<&str> = s.split('/').collect();
+    let (group, version, name) = match parts.as_slice() {
+        [version, name] => ("", *version, *name),
+        [group, version, name] if !group.is_empty() => (*group, *version, *name),
+        _ => return None,
+    };
+    if version.is_empty() || name.is_empty() {
+        return None;
+    }
+    Some((group.to_string(), version.to_string(), name.to_string()))
 }
 
 /// UserInfo holds information about the user who made the request
@@ -146,6 +452,97 @@ pub struct UserInfo {
     pub extra: HashMap<String, serde_json::Value>,
 }
 
+impl<T> ValidationRequest<T>
+where
+    T: Default,
+{
+    /// Creates a new `ValidationRequest` starting from the policy settings
+    /// and the admission request to be evaluated.
+    pub fn from_admission_request(settings: T, request: KubernetesAdmissionRequest) -> Self {
+        ValidationRequest { settings, request }
+    }
+
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+    #[cfg(feature = "cluster-context")]
+    /// Return the object of the request as the Kubernetes type `K`.
+    ///
+    /// An error is returned when the kind of the request does not match `K`,
+    /// when the request has no object (e.g. DELETE operations) or when the
+    /// object cannot be parsed.
+    ///
+    /// The object is parsed on each call, keep the returned value around
+    /// instead of calling this method multiple times.
+    pub fn object_as<K>(&self) -> anyhow::Result<K>
+    where
+        K: Resource + DeserializeOwned,
+    {
+        self.ensure_kind::<K>()?;
+        if self.request.object.is_null() {
+            return Err(anyhow!("the request does not have an object"));
+        }
+        typed_object(&self.request.object)
+    }
+
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+    #[cfg(feature = "cluster-context")]
+    /// Return the old object of the request as the Kubernetes type `K`.
+    /// The old object is only populated for DELETE and UPDATE requests,
+    /// `None` is returned otherwise.
+    ///
+    /// An error is returned when the kind of the request does not match `K`,
+    /// or when the old object cannot be parsed.
+    ///
+    /// The old object is parsed on each call.
+    pub fn old_object_as<K>(&self) -> anyhow::Result<Option<K>>
+    where
+        K: Resource + DeserializeOwned,
+    {
+        self.ensure_kind::<K>()?;
+        if self.request.old_object.is_null() {
+            return Ok(None);
+        }
+        typed_object(&self.request.old_object).map(Some)
+    }
+
+    #[cfg(feature = "cluster-context")]
+    fn ensure_kind<K: Resource>(&self) -> anyhow::Result<()> {
+        if !self.request.kind.is::<K>() {
+            return Err(anyhow!(
+                "the request is about {}, not {}",
+                self.request.kind,
+                GroupVersionKind::of::<K>(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Parse `value` into `K`
+#[cfg(feature = "cluster-context")]
+fn typed_object<K>(value: &serde_json::Value) -> anyhow::Result<K>
+where
+    K: Resource + DeserializeOwned,
+{
+    K::deserialize(value).map_err(|e| anyhow!("cannot parse object as {}: {}", K::KIND, e))
+}
+
+impl<T> ValidationRequest<T>
+where
+    T: Default + DeserializeOwned + Validatable,
+{
+    /// Crates a new `ValidationRequest` starting from the payload provided
+    /// to the policy at invocation time, then normalizes its settings, see
+    /// [`Validatable::normalize`].
+    pub fn new_normalized(payload: &[u8]) -> anyhow::Result<Self> {
+        let mut validation_request = Self::new(payload)?;
+        validation_request
+            .settings
+            .normalize()
+            .map_err(|e| anyhow!("Error normalizing settings: {}", e))?;
+        Ok(validation_request)
+    }
+}
+
 impl<T> ValidationRequest<T>
 where
     T: Default + DeserializeOwned,
@@ -169,43 +566,20 @@ where
     /// Objects supported are: Deployment, ReplicaSet, StatefulSet, DaemonSet, ReplicationController, Job, CronJob, Pod
     /// It returns an error if the object is not one of those. If it is a supported object it returns the PodSpec if present, otherwise returns None.
     pub fn extract_pod_spec_from_object(&self) -> anyhow::Result<Option<PodSpec>> {
-        match self.request.kind.kind.as_str() {
-            Deployment::KIND => {
-                let deployment = serde_json::from_value::<Deployment>(self.request.object.clone())?;
-                Ok(deployment.spec.and_then(|spec| spec.template.spec))
-            },
-            ReplicaSet::KIND => {
-                let replicaset = serde_json::from_value::<ReplicaSet>(self.request.object.clone())?;
-                Ok(replicaset.spec.and_then(|spec| spec.template.and_then(|template| template.spec)))
-            },
-            StatefulSet::KIND => {
-                let statefulset = serde_json::from_value::<StatefulSet>(self.request.object.clone())?;
-                Ok(statefulset.spec.and_then(|spec| spec.template.spec))
-            },
-            DaemonSet::KIND => {
-                let daemonset = serde_json::from_value::<DaemonSet>(self.request.object.clone())?;
-                Ok(daemonset.spec.and_then(|spec| spec.template.spec))
-            },
-            ReplicationController::KIND => {
-                let replication_controller = serde_json::from_value::<ReplicationController>(self.request.object.clone())?;
-                Ok(replication_controller.spec.and_then(|spec| spec.template.and_then(|template| template.spec)))
-            },
-            CronJob::KIND => {
-                let cronjob = serde_json::from_value::<CronJob>(self.request.object.clone())?;
-                Ok(cronjob.spec.and_then(|spec| spec.job_template.spec.and_then(|spec| spec.template.spec)))
-            },
-            Job::KIND => {
-                let job = serde_json::from_value::<Job>(self.request.object.clone())?;
-                Ok(job.spec.and_then(|spec| spec.template.spec))
-            },
-            Pod::KIND => {
-                let pod = serde_json::from_value::<Pod>(self.request.object.clone())?;
-                Ok(pod.spec)
-            },
-            _ => {
-                Err(anyhow!("Object should be one of these kinds: Deployment, ReplicaSet, StatefulSet, DaemonSet, ReplicationController, Job, CronJob, Pod"))
-            }
-        }
+        Workload::try_from_object(&self.request.kind.kind, &self.request.object)
+            .map(|workload| workload.pod_spec())
+    }
+
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+    #[cfg(feature = "cluster-context")]
+    /// Extract the pod template, metadata included, from high level objects.
+    /// This is like `extract_pod_spec_from_object`, but gives access to the labels
+    /// and annotations of the Pods created by the object. For Pods, the template is
+    /// made of the metadata and the spec of the Pod.
+    /// It returns an error if the object is not one of the supported kinds.
+    pub fn extract_pod_template_from_object(&self) -> anyhow::Result<Option<PodTemplateSpec>> {
+        Workload::try_from_object(&self.request.kind.kind, &self.request.object)
+            .map(|workload| workload.pod_template())
     }
 }
 
@@ -214,10 +588,12 @@ where
 mod tests {
     use super::*;
     use k8s_openapi::api::apps::v1::{
-        DaemonSetSpec, DeploymentSpec, ReplicaSetSpec, StatefulSetSpec,
+        DaemonSet, DaemonSetSpec, Deployment, DeploymentSpec, ReplicaSet, ReplicaSetSpec,
+        StatefulSet, StatefulSetSpec,
     };
-    use k8s_openapi::api::batch::v1::{CronJobSpec, JobSpec, JobTemplateSpec};
-    use k8s_openapi::api::core::v1::{ConfigMap, PodTemplateSpec};
+    use k8s_openapi::api::batch::v1::{CronJob, CronJobSpec, Job, JobSpec, JobTemplateSpec};
+    use k8s_openapi::api::core::v1::{ConfigMap, Pod};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
 
     use serde::Serialize;
 
@@ -416,6 +792,37 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_extract_pod_template_from_object() {
+        let template = PodTemplateSpec {
+            metadata: Some(ObjectMeta {
+                labels: Some([("app".to_string(), "nginx".to_string())].into()),
+                ..Default::default()
+            }),
+            spec: Some(PodSpec::default()),
+        };
+        let job = Job {
+            spec: Some(JobSpec {
+                template: template.clone(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let validation_request = create_validation_request(job, "Job");
+
+        assert_eq!(
+            validation_request
+                .extract_pod_template_from_object()
+                .unwrap(),
+            Some(template)
+        );
+
+        let validation_request = create_validation_request(ConfigMap::default(), "ConfigMap");
+        assert!(validation_request
+            .extract_pod_template_from_object()
+            .is_err());
+    }
+
     #[test]
     fn test_extract_pod_spec_from_object_not_supported() {
         let configmap = ConfigMap {
@@ -433,11 +840,261 @@ mod tests {
         assert!(validation_request.extract_pod_spec_from_object().is_err())
     }
 
+    #[test]
+    fn test_operation_serialization() {
+        let request: KubernetesAdmissionRequest =
+            serde_json::from_value(serde_json::json!({"operation": "UPDATE"})).unwrap();
+        assert_eq!(request.operation, Operation::Update);
+
+        let request: KubernetesAdmissionRequest =
+            serde_json::from_value(serde_json::json!({"operation": "PATCH"})).unwrap();
+        assert_eq!(request.operation, Operation::Unknown("PATCH".to_string()));
+
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["operation"], "PATCH");
+        assert_eq!(
+            serde_json::to_value(Operation::Connect).unwrap(),
+            serde_json::json!("CONNECT")
+        );
+    }
+
+    #[test]
+    fn test_operation_compared_with_strings() {
+        let request: KubernetesAdmissionRequest =
+            serde_json::from_value(serde_json::json!({"operation": "CREATE"})).unwrap();
+        assert!(request.operation == "CREATE");
+        assert!(request.operation != "UPDATE");
+        assert!(request.operation == *"CREATE");
+        let operation = String::from("CREATE");
+        assert!(request.operation == operation);
+        assert!(Operation::Unknown("PATCH".to_string()) == "PATCH");
+    }
+
+    #[test]
+    fn test_operation_options() {
+        let request: KubernetesAdmissionRequest = serde_json::from_value(serde_json::json!({
+            "operation": "CREATE",
+            "options": {
+                "apiVersion": "meta.k8s.io/v1",
+                "kind": "CreateOptions",
+                "fieldManager": "kubectl-client-side-apply"
+            }
+        }))
+        .unwrap();
+        match request.operation_options().unwrap() {
+            Some(OperationOptions::Create(options)) => assert_eq!(
+                options.field_manager,
+                Some("kubectl-client-side-apply".to_string())
+            ),
+            other => panic!("unexpected options: {:?}", other),
+        }
+
+        let request: KubernetesAdmissionRequest = serde_json::from_value(serde_json::json!({
+            "operation": "DELETE",
+            "options": {
+                "apiVersion": "meta.k8s.io/v1",
+                "kind": "DeleteOptions",
+                "propagationPolicy": "Background"
+            }
+        }))
+        .unwrap();
+        match request.operation_options().unwrap() {
+            Some(OperationOptions::Delete(options)) => {
+                assert_eq!(options.propagation_policy, Some("Background".to_string()))
+            }
+            other => panic!("unexpected options: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_operation_options_not_provided() {
+        let request = KubernetesAdmissionRequest {
+            operation: Operation::Update,
+            ..Default::default()
+        };
+        assert!(request.operation_options().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_group_version_resource_deserialization() {
+        let request: KubernetesAdmissionRequest = serde_json::from_value(serde_json::json!({
+            "resource": {"group": "apps", "version": "v1", "resource": "deployments"},
+            "requestResource": {"group": "apps", "version": "v1beta1", "resource": "deployments"}
+        }))
+        .unwrap();
+
+        assert_eq!(request.resource.to_string(), "apps/v1/deployments");
+        assert_eq!(
+            request.request_resource.to_string(),
+            "apps/v1beta1/deployments"
+        );
+        assert!(request.resource.is::<Deployment>());
+    }
+
+    #[test]
+    fn test_group_version_kind_from_str() {
+        let gvk: GroupVersionKind = "apps/v1/Deployment".parse().unwrap();
+        assert_eq!(gvk, GroupVersionKind::of::<Deployment>());
+        assert_eq!(gvk.to_string(), "apps/v1/Deployment");
+        assert_eq!(
+            gvk.to_api_version_and_kind(),
+            ("apps/v1".to_string(), "Deployment".to_string())
+        );
+
+        let gvk: GroupVersionKind = "v1/Pod".parse().unwrap();
+        assert!(gvk.is::<Pod>());
+        assert!(!gvk.is::<Deployment>());
+        assert_eq!(gvk.to_string(), "v1/Pod");
+        assert_eq!(gvk.api_version(), "v1");
+
+        for invalid in ["Pod", "", "/v1/Pod", "v1/", "a/b/c/d"] {
+            assert!(
+                invalid.parse::<GroupVersionKind>().is_err(),
+                "{} should not be parsed",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_group_version_resource_from_str() {
+        let gvr: GroupVersionResource = "v1/pods".parse().unwrap();
+        assert_eq!(gvr, GroupVersionResource::of::<Pod>());
+        assert_eq!(gvr.to_string(), "v1/pods");
+
+        let gvr: GroupVersionResource = "batch/v1/cronjobs".parse().unwrap();
+        assert!(gvr.is::<CronJob>());
+        assert_eq!(gvr.api_version(), "batch/v1");
+
+        assert!("pods".parse::<GroupVersionResource>().is_err());
+    }
+
+    #[test]
+    fn test_settings_are_normalized() {
+        #[derive(Deserialize, Default, Debug)]
+        struct Settings {
+            registry: String,
+        }
+
+        impl Validatable for Settings {
+            fn validate(&self) -> Result<(), String> {
+                Ok(())
+            }
+
+            fn normalize(&mut self) -> Result<(), String> {
+                if self.registry.is_empty() {
+                    return Err("registry must be set".to_string());
+                }
+                self.registry = self.registry.to_lowercase();
+                Ok(())
+            }
+        }
+
+        let payload = serde_json::json!({
+            "settings": {"registry": "Registry.Local"},
+            "request": {},
+        });
+        let validation_request =
+            ValidationRequest::<Settings>::new(payload.to_string().as_bytes()).unwrap();
+        assert_eq!(validation_request.settings.registry, "Registry.Local");
+
+        let validation_request =
+            ValidationRequest::<Settings>::new_normalized(payload.to_string().as_bytes()).unwrap();
+        assert_eq!(validation_request.settings.registry, "registry.local");
+
+        let payload = serde_json::json!({
+            "settings": {"registry": ""},
+            "request": {},
+        });
+        let err = ValidationRequest::<Settings>::new_normalized(payload.to_string().as_bytes())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Error normalizing settings: registry must be set"
+        );
+    }
+
+    fn create_pod_validation_request(old_object: serde_json::Value) -> ValidationRequest<()> {
+        let pod = Pod {
+            metadata: ObjectMeta {
+                name: Some("nginx".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        ValidationRequest::from_admission_request(
+            (),
+            KubernetesAdmissionRequest {
+                kind: GroupVersionKind {
+                    group: "".to_string(),
+                    version: "v1".to_string(),
+                    kind: "Pod".to_string(),
+                },
+                object: serde_json::to_value(pod).unwrap(),
+                old_object,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_object_as() {
+        let validation_request = create_pod_validation_request(serde_json::Value::Null);
+
+        let pod = validation_request.object_as::<Pod>().unwrap();
+        assert_eq!(pod.metadata.name, Some("nginx".to_string()));
+    }
+
+    #[test]
+    fn test_object_as_with_wrong_kind() {
+        let validation_request = create_pod_validation_request(serde_json::Value::Null);
+
+        let err = validation_request.object_as::<Deployment>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the request is about v1/Pod, not apps/v1/Deployment"
+        );
+    }
+
+    #[test]
+    fn test_object_as_without_object() {
+        let mut validation_request = create_pod_validation_request(serde_json::Value::Null);
+        validation_request.request.object = serde_json::Value::Null;
+
+        assert!(validation_request.object_as::<Pod>().is_err());
+    }
+
+    #[test]
+    fn test_object_as_with_invalid_object() {
+        let mut validation_request = create_pod_validation_request(serde_json::Value::Null);
+        validation_request.request.object = serde_json::json!({"spec": "invalid"});
+
+        assert!(validation_request.object_as::<Pod>().is_err());
+    }
+
+    #[test]
+    fn test_old_object_as() {
+        let validation_request = create_pod_validation_request(serde_json::Value::Null);
+        assert!(validation_request.old_object_as::<Pod>().unwrap().is_none());
+
+        let old_pod = Pod {
+            metadata: ObjectMeta {
+                name: Some("old-nginx".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let validation_request =
+            create_pod_validation_request(serde_json::to_value(old_pod).unwrap());
+        let old_pod = validation_request.old_object_as::<Pod>().unwrap().unwrap();
+        assert_eq!(old_pod.metadata.name, Some("old-nginx".to_string()));
+    }
+
     fn create_validation_request<T: Serialize>(object: T, kind: &str) -> ValidationRequest<()> {
         let value = serde_json::to_value(object).unwrap();
-        ValidationRequest {
-            settings: (),
-            request: KubernetesAdmissionRequest {
+        ValidationRequest::from_admission_request(
+            (),
+            KubernetesAdmissionRequest {
                 kind: GroupVersionKind {
                     kind: kind.to_string(),
                     ..Default::default()
@@ -445,6 +1102,6 @@ mod tests {
                 object: value,
                 ..Default::default()
             },
-        }
+        )
     }
 }
@@ -1,5 +1,10 @@
+use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+
+use crate::names::validate_qualified_name;
+use crate::request::PolicyMode;
 
 /// A ValidationResponse object holds the outcome of policy
 /// evaluation.
@@ -24,3 +29,621 @@ pub struct ValidationResponse {
     /// Warnings over 256 characters and large numbers of warnings may be truncated.
     pub warnings: Option<Vec<String>>,
 }
+
+/// Warnings longer than this are truncated
+pub const WARNING_MAX_LENGTH: usize = 256;
+
+/// Audit annotation holding the message of a rejection that has been turned
+/// into an acceptance because the policy runs in monitor mode
+pub const MONITOR_MODE_AUDIT_ANNOTATION: &str = "monitor-mode-rejection";
+
+/// Turn a rejection into an acceptance when the policy runs in monitor mode.
+/// The rejection message is reported via a warning and an audit annotation.
+pub(crate) fn apply_policy_mode(
+    mut response: ValidationResponse,
+    mode: &PolicyMode,
+) -> ValidationResponse {
+    if *mode == PolicyMode::Protect || response.accepted {
+        return response;
+    }
+
+    let message = response
+        .message
+        .take()
+        .unwrap_or_else(|| "no reason provided".to_string());
+    response.accepted = true;
+    response.code = None;
+    response
+        .warnings
+        .get_or_insert_with(Vec::new)
+        .push(truncate_warning(&format!(
+            "monitor mode: the request would have been rejected: {}",
+            message
+        )));
+    response
+        .audit_annotations
+        .get_or_insert_with(HashMap::new)
+        .insert(MONITOR_MODE_AUDIT_ANNOTATION.to_string(), message);
+    response
+}
+
+fn truncate_warning(warning: &str) -> String {
+    if warning.chars().count() > WARNING_MAX_LENGTH {
+        let mut truncated: String = warning.chars().take(WARNING_MAX_LENGTH - 3).collect();
+        truncated.push_str("...");
+        truncated
+    } else {
+        warning.to_string()
+    }
+}
+
+/// Fluent builder of the responses returned by the `validate` waPC function.
+///
+/// The builder enforces the limits documented on `ValidationResponse`:
+/// warnings longer than [`WARNING_MAX_LENGTH`] characters are truncated, and
+/// audit annotation keys must be valid qualified names (e.g. `error` or
+/// `imagepolicy.example.com/error`).
+///
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::response::ValidationResponseBuilder;
+///
+/// fn validate(_payload: &[u8]) -> wapc_guest::CallResult {
+///     ValidationResponseBuilder::accept()
+///         .warning("the nginx:latest image is deprecated")
+///         .audit_annotation("deprecated-image", "nginx:latest")
+///         .build()
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ValidationResponseBuilder {
+    response: ValidationResponse,
+    mode: PolicyMode,
+    errors: Vec<String>,
+}
+
+impl ValidationResponseBuilder {
+    fn new(accepted: bool) -> Self {
+        ValidationResponseBuilder {
+            response: ValidationResponse {
+                accepted,
+                message: None,
+                code: None,
+                mutated_object: None,
+                audit_annotations: None,
+                warnings: None,
+            },
+            mode: PolicyMode::default(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Start building a response that accepts the request
+    pub fn accept() -> Self {
+        Self::new(true)
+    }
+
+    /// Start building a response that rejects the request
+    /// # Arguments
+    /// * `message` - message shown to the user
+    pub fn reject(message: &str) -> Self {
+        Self::new(false).message(message)
+    }
+
+    /// Start building a response that accepts the request and mutates the
+    /// original object
+    /// # Arguments
+    /// * `mutated_object` - the mutated Object
+    pub fn mutate(mutated_object: serde_json::Value) -> Self {
+        let mut builder = Self::new(true);
+        builder.response.mutated_object = Some(mutated_object);
+        builder
+    }
+
+    /// Set the message shown to the user
+    pub fn message(mut self, message: &str) -> Self {
+        self.response.message = Some(message.to_string());
+        self
+    }
+
+    /// Set the code shown to the user
+    pub fn code(mut self, code: u16) -> Self {
+        self.response.code = Some(code);
+        self
+    }
+
+    /// Add a warning to be returned to the requesting API client. Warnings
+    /// longer than [`WARNING_MAX_LENGTH`] characters are truncated.
+    pub fn warning(mut self, warning: &str) -> Self {
+        self.response
+            .warnings
+            .get_or_insert_with(Vec::new)
+            .push(truncate_warning(warning));
+        self
+    }
+
+    /// Add an audit annotation. The key must be a valid qualified name,
+    /// otherwise `build` returns an error.
+    pub fn audit_annotation(mut self, key: &str, value: &str) -> Self {
+        if let Err(e) = validate_qualified_name(key) {
+            self.errors
+                .push(format!("invalid audit annotation key: {}", e));
+        }
+        self.response
+            .audit_annotations
+            .get_or_insert_with(HashMap::new)
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Set the execution mode of the policy, usually taken from the policy
+    /// settings. When running in monitor mode, a rejection
+    /// is turned into an acceptance: its message is reported via a warning
+    /// and the [`MONITOR_MODE_AUDIT_ANNOTATION`] audit annotation.
+    pub fn mode(mut self, mode: &PolicyMode) -> Self {
+        self.mode = mode.clone();
+        self
+    }
+
+    /// Build the `ValidationResponse`, returns an error when one of the
+    /// values provided is not valid
+    pub fn build_response(self) -> anyhow::Result<ValidationResponse> {
+        if !self.errors.is_empty() {
+            return Err(anyhow!(self.errors.join(", ")));
+        }
+        Ok(apply_policy_mode(self.response, &self.mode))
+    }
+
+    /// Build the response to be returned by the `validate` waPC function
+    pub fn build(self) -> wapc_guest::CallResult {
+        Ok(serde_json::to_vec(&self.build_response()?)?)
+    }
+}
+
+/// How serious a [`Violation`] is
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The request is rejected
+    #[default]
+    Error,
+    /// The request is accepted, the violation is reported to the user as a warning
+    Warning,
+}
+
+/// A problem found by the policy while evaluating a request
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Violation {
+    /// Path of the offending field, e.g. `spec.containers[0].image`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// Human readable description of the problem
+    pub message: String,
+    /// Machine readable identifier of the problem, e.g. `latest-tag`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub severity: Severity,
+}
+
+impl Violation {
+    /// Create a violation that causes the request to be rejected
+    pub fn error(field: Option<&str>, message: &str) -> Self {
+        Violation {
+            field: field.map(|f| f.to_string()),
+            message: message.to_string(),
+            code: None,
+            severity: Severity::Error,
+        }
+    }
+
+    /// Create a violation that is reported as a warning
+    pub fn warning(field: Option<&str>, message: &str) -> Self {
+        Violation {
+            severity: Severity::Warning,
+            ..Violation::error(field, message)
+        }
+    }
+
+    /// Set the machine readable identifier of the violation
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(field) = &self.field {
+            write!(f, "{}: ", field)?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(code) = &self.code {
+            write!(f, " ({})", code)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects all the violations found while evaluating a request, and turns
+/// them into a `ValidationResponse`.
+///
+/// The request is rejected when at least one violation with `Severity::Error`
+/// has been found. Violations with `Severity::Warning` are always returned
+/// as warnings.
+///
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::response::{Violation, Violations};
+///
+/// let mut violations = Violations::new();
+/// violations.add(
+///     Violation::error(Some("spec.containers[0].image"), "the latest tag is not allowed")
+///         .with_code("latest-tag"),
+/// );
+/// violations.add(Violation::warning(Some("spec.containers[0].resources"), "no limits set"));
+///
+/// let response = violations.with_audit_annotations().to_response();
+/// assert!(!response.accepted);
+/// assert_eq!(
+///     response.message.unwrap(),
+///     "spec.containers[0].image: the latest tag is not allowed (latest-tag)"
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Violations {
+    violations: Vec<Violation>,
+    mode: PolicyMode,
+    code: Option<u16>,
+    audit_annotations: bool,
+    errors_as_warnings: bool,
+}
+
+/// Audit annotation holding the JSON encoded list of violations
+pub const VIOLATIONS_AUDIT_ANNOTATION: &str = "violations";
+
+impl Violations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a violation
+    pub fn add(&mut self, violation: Violation) -> &mut Self {
+        self.violations.push(violation);
+        self
+    }
+
+    /// Record a violation that causes the request to be rejected
+    pub fn error(&mut self, field: Option<&str>, message: &str) -> &mut Self {
+        self.add(Violation::error(field, message))
+    }
+
+    /// Record a violation that is reported as a warning
+    pub fn warning(&mut self, field: Option<&str>, message: &str) -> &mut Self {
+        self.add(Violation::warning(field, message))
+    }
+
+    /// Code shown to the user when the request is rejected
+    pub fn code(&mut self, code: u16) -> &mut Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Report the violations inside of the audit annotations of the
+    /// response, under the [`VIOLATIONS_AUDIT_ANNOTATION`] key
+    pub fn with_audit_annotations(&mut self) -> &mut Self {
+        self.audit_annotations = true;
+        self
+    }
+
+    /// Report the violations causing the rejection as warnings too, so that
+    /// clients like kubectl show each one of them on its own line
+    pub fn with_errors_as_warnings(&mut self) -> &mut Self {
+        self.errors_as_warnings = true;
+        self
+    }
+
+    /// Set the execution mode of the policy, see [`ValidationResponseBuilder::mode`]
+    pub fn mode(&mut self, mode: &PolicyMode) -> &mut Self {
+        self.mode = mode.clone();
+        self
+    }
+
+    /// True if no violation has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// True if at least one violation causes the request to be rejected
+    pub fn has_errors(&self) -> bool {
+        self.violations
+            .iter()
+            .any(|v| v.severity == Severity::Error)
+    }
+
+    /// The violations sorted by severity, field, message and code. Sorting
+    /// ensures the same response is produced regardless of the order
+    /// used to record them.
+    pub fn sorted(&self) -> Vec<Violation> {
+        let mut violations = self.violations.clone();
+        violations.sort_by(|a, b| {
+            (a.severity, &a.field, &a.message, &a.code)
+                .cmp(&(b.severity, &b.field, &b.message, &b.code))
+        });
+        violations.dedup();
+        violations
+    }
+
+    /// The message describing all the violations causing the rejection,
+    /// `None` if there's none
+    pub fn message(&self) -> Option<String> {
+        let errors: Vec<String> = self
+            .sorted()
+            .iter()
+            .filter(|v| v.severity == Severity::Error)
+            .map(|v| v.to_string())
+            .collect();
+        if errors.is_empty() {
+            None
+        } else {
+            Some(errors.join("; "))
+        }
+    }
+
+    /// Build the `ValidationResponse` describing the violations
+    pub fn to_response(&self) -> ValidationResponse {
+        let violations = self.sorted();
+        let accepted = !self.has_errors();
+
+        let warnings: Vec<String> = violations
+            .iter()
+            .filter(|v| v.severity == Severity::Warning || self.errors_as_warnings)
+            .map(|v| truncate_warning(&v.to_string()))
+            .collect();
+
+        let audit_annotations = if self.audit_annotations && !violations.is_empty() {
+            Some(HashMap::from([(
+                VIOLATIONS_AUDIT_ANNOTATION.to_string(),
+                serde_json::to_string(&violations).unwrap_or_default(),
+            )]))
+        } else {
+            None
+        };
+
+        let response = ValidationResponse {
+            accepted,
+            message: self.message(),
+            code: if accepted { None } else { self.code },
+            mutated_object: None,
+            audit_annotations,
+            warnings: if warnings.is_empty() {
+                None
+            } else {
+                Some(warnings)
+            },
+        };
+        apply_policy_mode(response, &self.mode)
+    }
+
+    /// Build the response to be returned by the `validate` waPC function
+    pub fn to_call_result(&self) -> wapc_guest::CallResult {
+        Ok(serde_json::to_vec(&self.to_response())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn decode(raw_response: wapc_guest::CallResult) -> ValidationResponse {
+        serde_json::from_slice(&raw_response.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn builder_accept_with_warnings_and_audit_annotations() {
+        let response = decode(
+            ValidationResponseBuilder::accept()
+                .warning("first")
+                .warning("second")
+                .audit_annotation("imagepolicy.example.com/deprecated", "true")
+                .build(),
+        );
+
+        assert!(response.accepted);
+        assert_eq!(
+            response.warnings,
+            Some(vec!["first".to_string(), "second".to_string()])
+        );
+        assert_eq!(
+            response.audit_annotations,
+            Some(HashMap::from([(
+                "imagepolicy.example.com/deprecated".to_string(),
+                "true".to_string()
+            )]))
+        );
+    }
+
+    #[test]
+    fn builder_reject() {
+        let response = decode(
+            ValidationResponseBuilder::reject("not allowed")
+                .code(403)
+                .build(),
+        );
+
+        assert!(!response.accepted);
+        assert_eq!(response.message, Some("not allowed".to_string()));
+        assert_eq!(response.code, Some(403));
+        assert!(response.warnings.is_none());
+    }
+
+    #[test]
+    fn builder_mutate() {
+        let response = decode(
+            ValidationResponseBuilder::mutate(json!({"kind": "Pod"}))
+                .warning("mutated")
+                .build(),
+        );
+
+        assert!(response.accepted);
+        assert_eq!(response.mutated_object, Some(json!({"kind": "Pod"})));
+        assert_eq!(response.warnings, Some(vec!["mutated".to_string()]));
+    }
+
+    #[test]
+    fn builder_truncates_long_warnings() {
+        let response = ValidationResponseBuilder::accept()
+            .warning(&"é".repeat(300))
+            .build_response()
+            .unwrap();
+
+        let warning = &response.warnings.unwrap()[0];
+        assert_eq!(warning.chars().count(), WARNING_MAX_LENGTH);
+        assert!(warning.ends_with("..."));
+    }
+
+    #[test]
+    fn builder_rejects_invalid_audit_annotation_keys() {
+        let result = ValidationResponseBuilder::accept()
+            .audit_annotation("not a valid key", "value")
+            .build();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn builder_in_monitor_mode() {
+        let response = ValidationResponseBuilder::reject("privileged containers are not allowed")
+            .code(403)
+            .mode(&PolicyMode::Monitor)
+            .build_response()
+            .unwrap();
+
+        assert!(response.accepted);
+        assert!(response.message.is_none());
+        assert!(response.code.is_none());
+        assert_eq!(
+            response.warnings,
+            Some(vec!["monitor mode: the request would have been rejected: privileged containers are not allowed".to_string()])
+        );
+        assert_eq!(
+            response.audit_annotations.unwrap()[MONITOR_MODE_AUDIT_ANNOTATION],
+            "privileged containers are not allowed"
+        );
+    }
+
+    #[test]
+    fn builder_in_protect_mode() {
+        let response = ValidationResponseBuilder::reject("not allowed")
+            .mode(&PolicyMode::Protect)
+            .build_response()
+            .unwrap();
+
+        assert!(!response.accepted);
+        assert_eq!(response.message, Some("not allowed".to_string()));
+        assert!(response.warnings.is_none());
+    }
+
+    #[test]
+    fn violations_in_monitor_mode() {
+        let mut violations = Violations::new();
+        violations
+            .error(Some("spec.hostPID"), "host PID is not allowed")
+            .mode(&PolicyMode::Monitor);
+
+        let response = violations.to_response();
+        assert!(response.accepted);
+        assert_eq!(
+            response.audit_annotations.unwrap()[MONITOR_MODE_AUDIT_ANNOTATION],
+            "spec.hostPID: host PID is not allowed"
+        );
+    }
+
+    #[test]
+    fn no_violations() {
+        let response = Violations::new().to_response();
+        assert!(response.accepted);
+        assert!(response.message.is_none());
+        assert!(response.warnings.is_none());
+        assert!(response.audit_annotations.is_none());
+    }
+
+    #[test]
+    fn message_is_deterministic() {
+        let mut first = Violations::new();
+        first
+            .error(Some("spec.containers[1].image"), "latest tag")
+            .error(None, "missing owner label")
+            .error(Some("spec.containers[0].image"), "latest tag");
+
+        let mut second = Violations::new();
+        second
+            .error(Some("spec.containers[0].image"), "latest tag")
+            .error(Some("spec.containers[1].image"), "latest tag")
+            .error(None, "missing owner label");
+
+        assert_eq!(first.message(), second.message());
+        assert_eq!(
+            first.message().unwrap(),
+            "missing owner label; spec.containers[0].image: latest tag; spec.containers[1].image: latest tag"
+        );
+    }
+
+    #[test]
+    fn warnings_do_not_reject() {
+        let mut violations = Violations::new();
+        violations.warning(Some("spec.replicas"), "a single replica is not redundant");
+
+        let response = violations.to_response();
+        assert!(response.accepted);
+        assert!(response.message.is_none());
+        assert_eq!(
+            response.warnings,
+            Some(vec![
+                "spec.replicas: a single replica is not redundant".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn violation_warnings_are_truncated() {
+        let mut violations = Violations::new();
+        violations.warning(Some("metadata.name"), &"a".repeat(300));
+
+        let warnings = violations.to_response().warnings.unwrap();
+        assert_eq!(warnings[0].chars().count(), WARNING_MAX_LENGTH);
+        assert!(warnings[0].starts_with("metadata.name: aaa"));
+        assert!(warnings[0].ends_with("..."));
+    }
+
+    #[test]
+    fn rejection_with_code_warnings_and_audit_annotations() {
+        let mut violations = Violations::new();
+        violations
+            .add(
+                Violation::error(Some("spec.hostNetwork"), "host network is not allowed")
+                    .with_code("host-network"),
+            )
+            .warning(None, "deprecated API")
+            .code(403)
+            .with_errors_as_warnings()
+            .with_audit_annotations();
+
+        let response = violations.to_response();
+        assert!(!response.accepted);
+        assert_eq!(response.code, Some(403));
+        assert_eq!(
+            response.warnings,
+            Some(vec![
+                "spec.hostNetwork: host network is not allowed (host-network)".to_string(),
+                "deprecated API".to_string(),
+            ])
+        );
+
+        let annotation = &response.audit_annotations.unwrap()[VIOLATIONS_AUDIT_ANNOTATION];
+        let reported: Vec<Violation> = serde_json::from_str(annotation).unwrap();
+        assert_eq!(reported, violations.sorted());
+    }
+}
@@ -0,0 +1,305 @@
+use anyhow::anyhow;
+#[cfg(feature = "cluster-context")]
+use k8s_openapi::Resource;
+use serde::de::DeserializeOwned;
+use std::sync::OnceLock;
+
+use crate::request::{GroupVersionKind, Operation, ValidationRequest};
+use crate::settings::Validatable;
+use crate::{accept_request, reject_request};
+
+/// Function invoked by the [`Router`] to evaluate a request
+pub type Handler<T> = fn(&ValidationRequest<T>) -> wapc_guest::CallResult;
+
+/// What the [`Router`] does with requests that are not matched by any handler
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DefaultAction {
+    /// Accept the request
+    #[default]
+    Accept,
+    /// Reject the request, reporting the kind and the operation that
+    /// are not handled by the policy
+    Reject,
+}
+
+struct Route<T: Default> {
+    kind: GroupVersionKind,
+    operation: Option<Operation>,
+    handler: Handler<T>,
+}
+
+/// Router dispatches the requests received by the `validate` waPC function
+/// to the handler registered for the kind of the object and the operation
+/// being performed.
+///
+/// Handlers are evaluated in the order they have been registered, the first
+/// one matching the request is invoked.
+///
+/// Kinds are given as a [`GroupVersionKind`]. With the `cluster-context`
+/// feature, [`Router::on`] and [`Router::on_any_operation`] take the
+/// Kubernetes type instead.
+///
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::{accept_request, reject_request};
+/// use kubewarden_policy_sdk::request::{Operation, ValidationRequest};
+/// use kubewarden_policy_sdk::router::{DefaultAction, Router};
+/// use kubewarden_policy_sdk::settings::Validatable;
+///
+/// #[derive(serde::Deserialize, Default)]
+/// struct Settings {}
+///
+/// impl Validatable for Settings {
+///     fn validate(&self) -> Result<(), String> {
+///         Ok(())
+///     }
+/// }
+///
+/// fn validate_pod(request: &ValidationRequest<Settings>) -> wapc_guest::CallResult {
+///     if request.request.object["metadata"]["labels"].is_null() {
+///         return reject_request(Some("pods must have labels".to_string()), None, None, None);
+///     }
+///     accept_request()
+/// }
+///
+/// fn validate_deployment(_request: &ValidationRequest<Settings>) -> wapc_guest::CallResult {
+///     accept_request()
+/// }
+///
+/// // invoked from `wapc_init`
+/// Router::<Settings>::new()
+///     .on_kind("v1/Pod".parse().unwrap(), Operation::Create, validate_pod)
+///     .on_kind_any_operation("apps/v1/Deployment".parse().unwrap(), validate_deployment)
+///     .default_action(DefaultAction::Reject)
+///     .register()
+///     .expect("cannot register router");
+/// ```
+pub struct Router<T: Default> {
+    routes: Vec<Route<T>>,
+    default_action: DefaultAction,
+}
+
+impl<T: Default> Default for Router<T> {
+    fn default() -> Self {
+        Router {
+            routes: Vec::new(),
+            default_action: DefaultAction::default(),
+        }
+    }
+}
+
+type Dispatcher = Box<dyn Fn(&[u8]) -> wapc_guest::CallResult + Send + Sync>;
+
+/// The router registered via [`Router::register`]
+static VALIDATE_ROUTER: OnceLock<Dispatcher> = OnceLock::new();
+
+impl<T> Router<T>
+where
+    T: Default + DeserializeOwned + Validatable,
+{
+    /// Create a router without handlers, unmatched requests are accepted
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Invoke `handler` when the request is about `kind` and the given
+    /// operation is performed
+    pub fn on_kind(
+        mut self,
+        kind: GroupVersionKind,
+        operation: Operation,
+        handler: Handler<T>,
+    ) -> Self {
+        self.routes.push(Route {
+            kind,
+            operation: Some(operation),
+            handler,
+        });
+        self
+    }
+
+    /// Invoke `handler` when the request is about `kind`, regardless of the
+    /// operation being performed
+    pub fn on_kind_any_operation(mut self, kind: GroupVersionKind, handler: Handler<T>) -> Self {
+        self.routes.push(Route {
+            kind,
+            operation: None,
+            handler,
+        });
+        self
+    }
+
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+    #[cfg(feature = "cluster-context")]
+    /// Invoke `handler` when the request is about the Kubernetes type `K`
+    /// and the given operation is performed
+    pub fn on<K: Resource>(self, operation: Operation, handler: Handler<T>) -> Self {
+        self.on_kind(GroupVersionKind::of::<K>(), operation, handler)
+    }
+
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+    #[cfg(feature = "cluster-context")]
+    /// Invoke `handler` when the request is about the Kubernetes type `K`,
+    /// regardless of the operation being performed
+    pub fn on_any_operation<K: Resource>(self, handler: Handler<T>) -> Self {
+        self.on_kind_any_operation(GroupVersionKind::of::<K>(), handler)
+    }
+
+    /// Set what to do with requests that are not matched by any handler
+    pub fn default_action(mut self, default_action: DefaultAction) -> Self {
+        self.default_action = default_action;
+        self
+    }
+
+    /// Evaluate the payload given to the `validate` waPC function. The
+    /// settings are normalized before invoking the handler.
+    // `Option::is_none_or` would require Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn dispatch(&self, payload: &[u8]) -> wapc_guest::CallResult {
+        let validation_request = ValidationRequest::<T>::new_normalized(payload)?;
+        let request = &validation_request.request;
+
+        let route = self.routes.iter().find(|route| {
+            route.kind == request.kind
+                && route
+                    .operation
+                    .as_ref()
+                    .map_or(true, |operation| *operation == request.operation)
+        });
+        match route {
+            Some(route) => (route.handler)(&validation_request),
+            None => match self.default_action {
+                DefaultAction::Accept => accept_request(),
+                DefaultAction::Reject => reject_request(
+                    Some(format!(
+                        "{} operation on {} is not handled by this policy",
+                        request.operation, request.kind
+                    )),
+                    None,
+                    None,
+                    None,
+                ),
+            },
+        }
+    }
+}
+
+impl<T> Router<T>
+where
+    T: Default + DeserializeOwned + Validatable + 'static,
+{
+    /// Register the router as the `validate` waPC function. This has to be
+    /// done inside of `wapc_init`, only one router can be registered.
+    pub fn register(self) -> anyhow::Result<()> {
+        VALIDATE_ROUTER
+            .set(Box::new(move |payload| self.dispatch(payload)))
+            .map_err(|_| anyhow!("a router has already been registered"))?;
+        wapc_guest::register_function("validate", validate_with_router);
+        Ok(())
+    }
+}
+
+fn validate_with_router(payload: &[u8]) -> wapc_guest::CallResult {
+    let router = VALIDATE_ROUTER
+        .get()
+        .ok_or_else(|| anyhow!("no router has been registered"))?;
+    router(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::ValidationResponse;
+    use serde_json::json;
+
+    fn reject_pod(_: &ValidationRequest<()>) -> wapc_guest::CallResult {
+        reject_request(Some("pod".to_string()), None, None, None)
+    }
+
+    fn reject_deployment(_: &ValidationRequest<()>) -> wapc_guest::CallResult {
+        reject_request(Some("deployment".to_string()), None, None, None)
+    }
+
+    fn payload(group: &str, kind: &str, operation: &str) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "settings": null,
+            "request": {
+                "kind": {"group": group, "version": "v1", "kind": kind},
+                "operation": operation,
+            }
+        }))
+        .unwrap()
+    }
+
+    fn evaluate(router: &Router<()>, payload: &[u8]) -> ValidationResponse {
+        serde_json::from_slice(&router.dispatch(payload).unwrap()).unwrap()
+    }
+
+    fn kind(kind: &str) -> GroupVersionKind {
+        kind.parse().unwrap()
+    }
+
+    #[test]
+    fn dispatch_to_matching_handler() {
+        let router = Router::<()>::new()
+            .on_kind(kind("v1/Pod"), Operation::Create, reject_pod)
+            .on_kind_any_operation(kind("apps/v1/Deployment"), reject_deployment);
+
+        let response = evaluate(&router, &payload("", "Pod", "CREATE"));
+        assert_eq!(response.message, Some("pod".to_string()));
+
+        let response = evaluate(&router, &payload("apps", "Deployment", "DELETE"));
+        assert_eq!(response.message, Some("deployment".to_string()));
+    }
+
+    #[test]
+    fn unmatched_requests_are_accepted_by_default() {
+        let router = Router::<()>::new().on_kind(kind("v1/Pod"), Operation::Create, reject_pod);
+
+        let response = evaluate(&router, &payload("", "Pod", "UPDATE"));
+        assert!(response.accepted);
+
+        let response = evaluate(&router, &payload("", "ConfigMap", "CREATE"));
+        assert!(response.accepted);
+    }
+
+    #[test]
+    fn unmatched_requests_can_be_rejected() {
+        let router = Router::<()>::new()
+            .on_kind(kind("v1/Pod"), Operation::Create, reject_pod)
+            .default_action(DefaultAction::Reject);
+
+        let response = evaluate(&router, &payload("apps", "Deployment", "CREATE"));
+        assert!(!response.accepted);
+        assert_eq!(
+            response.message,
+            Some(
+                "CREATE operation on apps/v1/Deployment is not handled by this policy".to_string()
+            )
+        );
+    }
+
+    #[cfg(feature = "cluster-context")]
+    #[test]
+    fn dispatch_by_kubernetes_type() {
+        use k8s_openapi::api::apps::v1::Deployment;
+        use k8s_openapi::api::core::v1::Pod;
+
+        let router = Router::<()>::new()
+            .on::<Pod>(Operation::Create, reject_pod)
+            .on_any_operation::<Deployment>(reject_deployment);
+
+        let response = evaluate(&router, &payload("", "Pod", "CREATE"));
+        assert_eq!(response.message, Some("pod".to_string()));
+
+        let response = evaluate(&router, &payload("apps", "Deployment", "DELETE"));
+        assert_eq!(response.message, Some("deployment".to_string()));
+    }
+
+    #[test]
+    fn dispatch_invalid_payload() {
+        let router = Router::<()>::new();
+        assert!(router.dispatch(b"not json").is_err());
+    }
+}
@@ -1,14 +1,188 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+
+#[cfg(feature = "cluster-context")]
+use crate::host_capabilities::kubernetes;
+use crate::host_capabilities::{crypto, net, oci};
 
 /// Trait that must be implemented by setting
 /// object
 pub trait Validatable {
     /// Ensures the values given by the user are valid
     fn validate(&self) -> Result<(), String>;
+
+    /// Like [`Validatable::validate`], but reports all the problems found,
+    /// each one with the path of the offending field. This is the method
+    /// invoked by [`crate::validate_settings`].
+    ///
+    /// By default the message returned by [`Validatable::validate`] is
+    /// reported. When this method is implemented, `validate` can simply be
+    /// `self.validate_fields().map_err(|e| e.to_string())`.
+    fn validate_fields(&self) -> Result<(), SettingsErrors> {
+        self.validate().map_err(SettingsErrors::from)
+    }
+
+    /// Normalize the settings right after they have been deserialized, before
+    /// they are validated. This can be used to fill defaults, to lowercase
+    /// values or to compile regular expressions once and store them inside of
+    /// a `#[serde(skip)]` field.
+    ///
+    /// The hook is invoked both by [`crate::validate_settings`] and by
+    /// [`crate::request::ValidationRequest::new_normalized`], hence the
+    /// settings seen at evaluation time are the ones that have been validated.
+    /// By default the settings are left untouched.
+    fn normalize(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Policies without settings
+impl Validatable for () {
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Variant of [`Validatable`] for settings that can be validated only by
+/// consulting the host: e.g. to ensure a referenced ConfigMap exists, or that
+/// an image digest can be resolved.
+///
+/// Register [`crate::validate_settings_with_context`] as `validate_settings`
+/// to use it. The local checks of [`Validatable`] are performed first.
+pub trait ContextAwareValidatable: Validatable {
+    /// Ensures the values given by the user are valid, using the host
+    /// capabilities offered by `context`
+    fn validate_with_context(&self, context: &SettingsContext) -> Result<(), SettingsErrors>;
+}
+
+/// Host capabilities available while validating the settings, see
+/// [`ContextAwareValidatable`]
+#[derive(Debug, Default)]
+pub struct SettingsContext {
+    _private: (),
+}
+
+impl SettingsContext {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a Kubernetes resource, see [`kubernetes::get_resource`]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+    #[cfg(feature = "cluster-context")]
+    pub fn get_resource<T>(&self, req: &kubernetes::GetResourceRequest) -> anyhow::Result<T>
+    where
+        T: DeserializeOwned + Clone,
+    {
+        kubernetes::get_resource(req)
+    }
+
+    /// Compute the digest of an OCI object, see [`oci::get_manifest_digest`]
+    pub fn get_manifest_digest(&self, image: &str) -> anyhow::Result<oci::ManifestDigestResponse> {
+        oci::get_manifest_digest(image)
+    }
+
+    /// Verify a certificate, see [`crypto::verify_cert`]
+    pub fn verify_cert(
+        &self,
+        cert: crypto::Certificate,
+        cert_chain: Option<Vec<crypto::Certificate>>,
+        not_after: Option<String>,
+    ) -> anyhow::Result<crypto::BoolWithReason> {
+        crypto::verify_cert(cert, cert_chain, not_after)
+    }
+
+    /// Resolve a host name, see [`net::lookup_host`]
+    pub fn lookup_host(&self, host: &str) -> anyhow::Result<net::LookupResponse> {
+        net::lookup_host(host)
+    }
+}
+
+/// A problem found inside of the settings
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FieldError {
+    /// Path of the offending field (e.g. `registries[1].name`), empty when
+    /// the problem is about the settings as a whole
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.field.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.field, self.message)
+        }
+    }
+}
+
+/// The problems found while validating the settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SettingsErrors {
+    errors: Vec<FieldError>,
+}
+
+impl SettingsErrors {
+    /// Create an empty list of errors
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report a problem about `field`, use an empty path for problems about
+    /// the settings as a whole
+    pub fn add(&mut self, field: &str, message: &str) -> &mut Self {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message: message.to_string(),
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn errors(&self) -> &[FieldError] {
+        &self.errors
+    }
+
+    /// `Ok` when no problem has been reported
+    pub fn into_result(self) -> Result<(), SettingsErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Display for SettingsErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors: Vec<String> = self.errors.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", errors.join("; "))
+    }
+}
+
+impl std::error::Error for SettingsErrors {}
+
+impl From<String> for SettingsErrors {
+    fn from(message: String) -> Self {
+        let mut errors = SettingsErrors::new();
+        errors.add("", &message);
+        errors
+    }
+}
+
+impl From<Vec<FieldError>> for SettingsErrors {
+    fn from(errors: Vec<FieldError>) -> Self {
+        SettingsErrors { errors }
+    }
 }
 
 /// A SettingsValidationResponse object holds the outcome of settings
-/// validation.
+/// validation. It can be built with [`SettingsValidationResponse::valid`]
+/// and [`SettingsValidationResponse::invalid`].
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct SettingsValidationResponse {
     /// True if the settings are valid
@@ -16,3 +190,279 @@ pub struct SettingsValidationResponse {
     /// Message shown to the user when the settings are not valid
     pub message: Option<String>,
 }
+
+impl SettingsValidationResponse {
+    /// Response reporting the settings as valid
+    pub fn valid() -> Self {
+        SettingsValidationResponse {
+            valid: true,
+            message: None,
+        }
+    }
+
+    /// Response reporting the settings as not valid because of `errors`,
+    /// all of them are reported inside of the message.
+    /// Use `SettingsErrors::from(message)` to report a single problem about
+    /// the settings as a whole
+    pub fn invalid(errors: SettingsErrors) -> Self {
+        SettingsValidationResponse {
+            valid: false,
+            message: Some(errors.to_string()),
+        }
+    }
+}
+
+impl From<Result<(), SettingsErrors>> for SettingsValidationResponse {
+    fn from(result: Result<(), SettingsErrors>) -> Self {
+        match result {
+            Ok(()) => SettingsValidationResponse::valid(),
+            Err(errors) => SettingsValidationResponse::invalid(errors),
+        }
+    }
+}
+
+/// Deserialize and normalize the settings, see [`Validatable::normalize`].
+/// The deserialization error reports the path of the field that cannot be
+/// decoded.
+pub fn load_settings<T>(payload: &[u8]) -> Result<T, SettingsErrors>
+where
+    T: DeserializeOwned + Validatable,
+{
+    let mut settings: T = deserialize_settings(payload)?;
+    settings.normalize().map_err(SettingsErrors::from)?;
+    Ok(settings)
+}
+
+/// Deserialize the settings, the error reports the path of the field that
+/// cannot be decoded
+pub fn deserialize_settings<T: DeserializeOwned>(payload: &[u8]) -> Result<T, SettingsErrors> {
+    let deserializer = &mut serde_json::Deserializer::from_slice(payload);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let field = match e.path().to_string() {
+            path if path == "." => String::new(),
+            path => path,
+        };
+        let mut errors = SettingsErrors::new();
+        errors.add(&field, &e.into_inner().to_string());
+        errors
+    })
+}
+
+/// Returns the JSON Schema describing the settings type `T`
+#[cfg_attr(docsrs, doc(cfg(feature = "settings-schema")))]
+#[cfg(feature = "settings-schema")]
+pub fn settings_schema<T: schemars::JsonSchema>() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(T)).expect("a JSON Schema can always be serialized")
+}
+
+/// Ensure `settings` conforms to the JSON Schema of the settings type `T`.
+/// Returns one error for each problem found, with the path of the offending
+/// field (e.g. `registries[1]`).
+#[cfg_attr(docsrs, doc(cfg(feature = "settings-schema")))]
+#[cfg(feature = "settings-schema")]
+pub fn validate_against_schema<T: schemars::JsonSchema>(
+    settings: &serde_json::Value,
+) -> Result<(), SettingsErrors> {
+    let schema = settings_schema::<T>();
+    let validator = jsonschema::validator_for(&schema)
+        .map_err(|e| SettingsErrors::from(format!("invalid settings schema: {}", e)))?;
+
+    let errors: Vec<FieldError> = validator
+        .iter_errors(settings)
+        .map(|e| FieldError {
+            field: json_pointer_to_field_path(settings, e.instance_path.as_str()),
+            message: e.to_string(),
+        })
+        .collect();
+    SettingsErrors::from(errors).into_result()
+}
+
+/// Turn a JSON pointer (`/registries/1/name`) into the path notation used by
+/// the SDK (`registries[1].name`). The pointer is resolved against
+/// `instance`: numeric segments are rendered as indexes only when they refer
+/// to an array item, map keys like `8080` are kept as they are
+/// (`ports.8080`)
+#[cfg(feature = "settings-schema")]
+fn json_pointer_to_field_path(instance: &serde_json::Value, pointer: &str) -> String {
+    let mut node = Some(instance);
+    pointer
+        .split('/')
+        .skip(1)
+        .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+        .fold(String::new(), |mut path, segment| {
+            match node {
+                Some(serde_json::Value::Array(items)) => {
+                    path.push_str(&format!("[{}]", segment));
+                    node = segment.parse::<usize>().ok().and_then(|i| items.get(i));
+                }
+                _ => {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(&segment);
+                    node = node.and_then(|n| n.get(&segment));
+                }
+            }
+            path
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "settings-schema")]
+    use serde_json::json;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, Debug)]
+    struct Limits {
+        replicas: Vec<u32>,
+    }
+
+    #[test]
+    fn deserialization_errors_report_the_path() {
+        let errors = deserialize_settings::<Limits>(br#"{"replicas": [1, "two"]}"#).unwrap_err();
+        assert_eq!(errors.errors().len(), 1);
+        assert_eq!(errors.errors()[0].field, "replicas[1]");
+        assert!(errors
+            .to_string()
+            .starts_with("replicas[1]: invalid type: string"));
+
+        let errors = deserialize_settings::<Limits>(b"{}").unwrap_err();
+        assert_eq!(errors.errors()[0].field, "");
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Registries {
+        registries: Vec<String>,
+    }
+
+    impl Validatable for Registries {
+        fn validate(&self) -> Result<(), String> {
+            match self
+                .registries
+                .iter()
+                .find(|r| r.contains(char::is_uppercase))
+            {
+                Some(r) => Err(format!("{} is not lowercase", r)),
+                None => Ok(()),
+            }
+        }
+
+        fn normalize(&mut self) -> Result<(), String> {
+            if self.registries.is_empty() {
+                return Err("at least one registry must be provided".to_string());
+            }
+            for registry in self.registries.iter_mut() {
+                *registry = registry.to_lowercase();
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn settings_are_normalized_when_loaded() {
+        let settings =
+            load_settings::<Registries>(br#"{"registries": ["Registry.Local"]}"#).unwrap();
+        assert_eq!(settings.registries, vec!["registry.local"]);
+        assert!(settings.validate().is_ok());
+
+        let errors = load_settings::<Registries>(br#"{"registries": []}"#).unwrap_err();
+        assert_eq!(errors.to_string(), "at least one registry must be provided");
+    }
+
+    #[test]
+    fn invalid_response_renders_all_errors() {
+        let mut errors = SettingsErrors::new();
+        errors
+            .add("registries[0]", "must not be empty")
+            .add("", "at least one of a and b must be set");
+
+        let response = SettingsValidationResponse::invalid(errors);
+        assert!(!response.valid);
+        assert_eq!(
+            response.message,
+            Some(
+                "registries[0]: must not be empty; at least one of a and b must be set".to_string()
+            )
+        );
+        assert!(SettingsValidationResponse::from(SettingsErrors::new().into_result()).valid);
+    }
+
+    #[test]
+    fn response_built_with_a_struct_literal() {
+        let response = SettingsValidationResponse {
+            valid: false,
+            message: Some("invalid settings".to_string()),
+        };
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({"valid": false, "message": "invalid settings"})
+        );
+    }
+
+    #[cfg(feature = "settings-schema")]
+    #[allow(dead_code)]
+    #[derive(Deserialize, schemars::JsonSchema)]
+    #[serde(rename_all = "camelCase")]
+    struct Registry {
+        name: String,
+        insecure: Option<bool>,
+    }
+
+    #[cfg(feature = "settings-schema")]
+    #[allow(dead_code)]
+    #[derive(Deserialize, schemars::JsonSchema)]
+    #[serde(rename_all = "camelCase", deny_unknown_fields)]
+    struct Settings {
+        allowed_registries: Vec<Registry>,
+        max_replicas: Option<u32>,
+    }
+
+    #[cfg(feature = "settings-schema")]
+    #[test]
+    fn schema_of_settings() {
+        let schema = settings_schema::<Settings>();
+        assert_eq!(schema["title"], "Settings");
+        assert_eq!(schema["required"], json!(["allowedRegistries"]));
+    }
+
+    #[cfg(feature = "settings-schema")]
+    #[test]
+    fn valid_settings() {
+        assert!(validate_against_schema::<Settings>(&json!({
+            "allowedRegistries": [{"name": "registry.local"}],
+            "maxReplicas": 3
+        }))
+        .is_ok());
+    }
+
+    #[cfg(feature = "settings-schema")]
+    #[test]
+    fn invalid_settings_report_field_paths() {
+        let errors = validate_against_schema::<Settings>(&json!({
+            "allowedRegistries": [{"name": "registry.local"}, {"name": 42}],
+            "maxReplicas": -1
+        }))
+        .unwrap_err();
+        let mut fields: Vec<&str> = errors.errors().iter().map(|e| e.field.as_str()).collect();
+        fields.sort();
+
+        assert_eq!(fields, vec!["allowedRegistries[1].name", "maxReplicas"]);
+    }
+
+    #[cfg(feature = "settings-schema")]
+    #[test]
+    fn json_pointers() {
+        let instance = json!({"a": [{"b/c": 1}], "ports": {"8080": {"name": "http"}}});
+        assert_eq!(json_pointer_to_field_path(&instance, ""), "");
+        assert_eq!(
+            json_pointer_to_field_path(&instance, "/a/0/b~1c"),
+            "a[0].b/c"
+        );
+        assert_eq!(
+            json_pointer_to_field_path(&instance, "/ports/8080/name"),
+            "ports.8080.name"
+        );
+    }
+}
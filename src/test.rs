@@ -1,8 +1,24 @@
+use crate::host_capabilities::transport::{self, HostCall, HostTransport};
 use crate::response::ValidationResponse;
-use serde::{de::DeserializeOwned, Serialize};
+use anyhow::anyhow;
+#[cfg(feature = "test-harness")]
+use regex::Regex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::json;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs::File;
 use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+#[cfg(feature = "cluster-context")]
+mod admission_request;
+#[cfg_attr(docsrs, doc(cfg(feature = "cluster-context")))]
+#[cfg(feature = "cluster-context")]
+pub use admission_request::AdmissionRequestBuilder;
 
 fn read_request_file(path: &str) -> anyhow::Result<serde_json::Value> {
     let file = File::open(path)?;
@@ -13,17 +29,18 @@ fn read_request_file(path: &str) -> anyhow::Result<serde_json::Value> {
     Ok(v)
 }
 
-fn make_validate_payload<T>(request_file: &str, settings: &T) -> String
+fn make_validate_payload<T>(request_file: &str, settings: &T) -> anyhow::Result<String>
 where
     T: DeserializeOwned + Serialize,
 {
-    let req = read_request_file(request_file).unwrap();
+    let req = read_request_file(request_file)
+        .map_err(|e| anyhow!("cannot read fixture file {}: {}", request_file, e))?;
     let payload = json!({
         "settings": settings,
         "request": req
     });
 
-    payload.to_string()
+    Ok(payload.to_string())
 }
 
 #[allow(dead_code)]
@@ -44,16 +61,983 @@ impl<T> Testcase<T>
 where
     T: DeserializeOwned + Serialize,
 {
+    /// Evaluate the test case.
+    ///
+    /// An error is returned when the policy cannot be evaluated, or when its
+    /// response cannot be parsed.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the outcome of the validation is not the expected one.
+    /// Use [`Testcase::check`] to get all the problems reported as a
+    /// [`TestFailure`] instead.
     pub fn eval(&self, validate: ValidateFn) -> anyhow::Result<ValidationResponse> {
-        let payload = make_validate_payload(self.fixture_file.as_str(), &self.settings);
-        let raw_result = validate(payload.as_bytes()).unwrap();
-        let response: ValidationResponse = serde_json::from_slice(&raw_result)?;
+        let response = self.evaluate(validate, None)?;
+        self.assert_outcome(&response);
+        Ok(response)
+    }
+
+    /// Like [`Testcase::eval`], but the host calls made by the policy are
+    /// answered by `host_calls`. An error is also returned when the policy
+    /// performs a host call that has no fixture.
+    pub fn eval_with_host_calls(
+        &self,
+        validate: ValidateFn,
+        host_calls: &HostCallFixtures,
+    ) -> anyhow::Result<ValidationResponse> {
+        let response = self.evaluate(validate, Some(host_calls))?;
+        self.assert_outcome(&response);
+        Ok(response)
+    }
+
+    fn assert_outcome(&self, response: &ValidationResponse) {
         assert_eq!(
             response.accepted, self.expected_validation_result,
             "Failure for test case: '{}': got {:?} instead of {:?}",
             self.name, response.accepted, self.expected_validation_result,
         );
+    }
+
+    /// Evaluate the test case and ensure the response fulfills `expectations`.
+    /// All the problems found are reported at once.
+    pub fn check(
+        &self,
+        validate: ValidateFn,
+        expectations: &Expectations,
+    ) -> Result<ValidationResponse, TestFailure> {
+        self.run(validate, expectations, None)
+    }
+
+    /// Like [`Testcase::check`], but the host calls made by the policy are
+    /// answered by `host_calls`
+    pub fn check_with_host_calls(
+        &self,
+        validate: ValidateFn,
+        expectations: &Expectations,
+        host_calls: &HostCallFixtures,
+    ) -> Result<ValidationResponse, TestFailure> {
+        self.run(validate, expectations, Some(host_calls))
+    }
+
+    fn run(
+        &self,
+        validate: ValidateFn,
+        expectations: &Expectations,
+        host_calls: Option<&HostCallFixtures>,
+    ) -> Result<ValidationResponse, TestFailure> {
+        let response = self.evaluate(validate, host_calls)?;
+        check_response(
+            &self.name,
+            response,
+            self.expected_validation_result,
+            expectations,
+        )
+    }
+
+    fn evaluate(
+        &self,
+        validate: ValidateFn,
+        host_calls: Option<&HostCallFixtures>,
+    ) -> Result<ValidationResponse, TestFailure> {
+        let payload = make_validate_payload(self.fixture_file.as_str(), &self.settings)
+            .map_err(|e| TestFailure::new(&self.name, vec![e.to_string()]))?;
+        evaluate_payload(&self.name, validate, &payload, host_calls)
+    }
+}
+
+/// Evaluate `payload` and check the response, the host calls are answered
+/// by `host_calls` when given
+fn run_payload(
+    name: &str,
+    validate: ValidateFn,
+    payload: &str,
+    expected_validation_result: bool,
+    expectations: &Expectations,
+    host_calls: Option<&HostCallFixtures>,
+) -> Result<ValidationResponse, TestFailure> {
+    let response = evaluate_payload(name, validate, payload, host_calls)?;
+    check_response(name, response, expected_validation_result, expectations)
+}
+
+/// Evaluate `payload` and parse the response, the host calls are answered
+/// by `host_calls` when given
+fn evaluate_payload(
+    name: &str,
+    validate: ValidateFn,
+    payload: &str,
+    host_calls: Option<&HostCallFixtures>,
+) -> Result<ValidationResponse, TestFailure> {
+    let raw_result = match host_calls {
+        Some(host_calls) => {
+            let fixtures = Rc::new(FixtureTransport {
+                fixtures: host_calls.clone(),
+                unanswered: RefCell::new(Vec::new()),
+            });
+            let raw_result = {
+                let _guard = transport::install(fixtures.clone());
+                validate(payload.as_bytes())
+            };
+
+            let unanswered = fixtures.unanswered.borrow();
+            if !unanswered.is_empty() {
+                return Err(TestFailure::new(
+                    name,
+                    vec![format!(
+                        "host calls without a fixture: {}",
+                        unanswered
+                            .iter()
+                            .map(|call| format!(
+                                "{}/{} {}",
+                                call.namespace,
+                                call.operation,
+                                String::from_utf8_lossy(&call.payload)
+                            ))
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    )],
+                ));
+            }
+            raw_result
+        }
+        None => validate(payload.as_bytes()),
+    };
 
+    let raw_result = raw_result
+        .map_err(|e| TestFailure::new(name, vec![format!("validation failed: {}", e)]))?;
+    serde_json::from_slice(&raw_result)
+        .map_err(|e| TestFailure::new(name, vec![format!("invalid validation response: {}", e)]))
+}
+
+/// Ensure `response` has the expected outcome and fulfills `expectations`
+fn check_response(
+    name: &str,
+    response: ValidationResponse,
+    expected_validation_result: bool,
+    expectations: &Expectations,
+) -> Result<ValidationResponse, TestFailure> {
+    let mut failures = Vec::new();
+    if response.accepted != expected_validation_result {
+        failures.push(format!(
+            "got accepted {:?} instead of {:?}, message: {:?}",
+            response.accepted, expected_validation_result, response.message
+        ));
+    }
+    failures.extend(expectations.check(&response));
+
+    if failures.is_empty() {
         Ok(response)
+    } else {
+        Err(TestFailure::new(name, failures))
+    }
+}
+
+/// Returned when the response of a [`Testcase`] is not the expected one
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestFailure {
+    /// Name of the test case
+    pub name: String,
+    /// Description of every problem found
+    pub failures: Vec<String>,
+}
+
+impl TestFailure {
+    fn new(name: &str, failures: Vec<String>) -> Self {
+        TestFailure {
+            name: name.to_string(),
+            failures,
+        }
+    }
+}
+
+impl fmt::Display for TestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "test case '{}' failed: {}",
+            self.name,
+            self.failures.join("; ")
+        )
+    }
+}
+
+impl std::error::Error for TestFailure {}
+
+/// Expectation about the message of the response
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum MessageExpectation {
+    /// The message must be exactly this one
+    Exact(String),
+    /// The message must match this regular expression. Checking it requires
+    /// the `test-harness` feature, otherwise the expectation is reported as
+    /// unmet.
+    Regex(String),
+}
+
+/// Expectations about the response of a [`Testcase`], besides the request
+/// being accepted or rejected. Unset fields are not checked.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Expectations {
+    /// Written as `{"exact": "..."}` or `{"regex": "..."}`
+    #[serde(default, with = "serde_yaml::with::singleton_map")]
+    pub message: Option<MessageExpectation>,
+    #[serde(default)]
+    pub code: Option<u16>,
+    /// The warnings of the response, in order
+    #[serde(default)]
+    pub warnings: Option<Vec<String>>,
+    #[serde(default)]
+    pub audit_annotations: Option<HashMap<String, String>>,
+    /// The mutated object, compared as a whole
+    #[serde(default)]
+    pub mutated_object: Option<serde_json::Value>,
+    /// Values expected inside of the mutated object, indexed by JSONPath
+    /// (e.g. `$.metadata.labels.owner`). When the path selects a single
+    /// value it is compared with the expected one, otherwise the list of the
+    /// selected values is compared.
+    ///
+    /// Checking them requires the `test-harness` feature, otherwise the
+    /// expectations are reported as unmet.
+    #[serde(default)]
+    pub mutated_object_paths: BTreeMap<String, serde_json::Value>,
+}
+
+impl Expectations {
+    /// Check `response`, returning the description of every unmet expectation
+    pub fn check(&self, response: &ValidationResponse) -> Vec<String> {
+        let mut failures = Vec::new();
+
+        match &self.message {
+            Some(MessageExpectation::Exact(expected))
+                if response.message.as_ref() != Some(expected) =>
+            {
+                failures.push(format!(
+                    "got message {:?} instead of {:?}",
+                    response.message, expected
+                ));
+            }
+            #[cfg(feature = "test-harness")]
+            Some(MessageExpectation::Regex(expected)) => match Regex::new(expected) {
+                Ok(re) => {
+                    if !response.message.as_ref().is_some_and(|m| re.is_match(m)) {
+                        failures.push(format!(
+                            "got message {:?} not matching {:?}",
+                            response.message, expected
+                        ));
+                    }
+                }
+                Err(e) => failures.push(format!("invalid message regex {:?}: {}", expected, e)),
+            },
+            #[cfg(not(feature = "test-harness"))]
+            Some(MessageExpectation::Regex(expected)) => failures.push(format!(
+                "cannot match message against {:?}: regex requires the test-harness feature",
+                expected
+            )),
+            _ => {}
+        }
+
+        if self.code.is_some() && self.code != response.code {
+            failures.push(format!(
+                "got code {:?} instead of {:?}",
+                response.code, self.code
+            ));
+        }
+
+        if let Some(expected) = &self.warnings {
+            let warnings = response.warnings.clone().unwrap_or_default();
+            if &warnings != expected {
+                failures.push(format!(
+                    "got warnings {:?} instead of {:?}",
+                    warnings, expected
+                ));
+            }
+        }
+
+        if let Some(expected) = &self.audit_annotations {
+            let audit_annotations = response.audit_annotations.clone().unwrap_or_default();
+            if &audit_annotations != expected {
+                failures.push(format!(
+                    "got audit annotations {:?} instead of {:?}",
+                    audit_annotations, expected
+                ));
+            }
+        }
+
+        if !self.expects_mutation() {
+            return failures;
+        }
+        let Some(mutated_object) = &response.mutated_object else {
+            failures.push("the request has not been mutated".to_string());
+            return failures;
+        };
+
+        if let Some(expected) = &self.mutated_object {
+            if mutated_object != expected {
+                failures.push(format!(
+                    "got mutated object {} instead of {}",
+                    mutated_object, expected
+                ));
+            }
+        }
+
+        #[cfg(feature = "test-harness")]
+        for (path, expected) in &self.mutated_object_paths {
+            match jsonpath_lib::select(mutated_object, path) {
+                Ok(selected) => {
+                    let actual = match selected.as_slice() {
+                        [value] => (*value).clone(),
+                        values => {
+                            serde_json::Value::Array(values.iter().map(|v| (*v).clone()).collect())
+                        }
+                    };
+                    if &actual != expected {
+                        failures.push(format!(
+                            "got {} at {} of the mutated object instead of {}",
+                            actual, path, expected
+                        ));
+                    }
+                }
+                Err(e) => failures.push(format!("invalid JSONPath {}: {:?}", path, e)),
+            }
+        }
+
+        #[cfg(not(feature = "test-harness"))]
+        for path in self.mutated_object_paths.keys() {
+            failures.push(format!(
+                "cannot check {} of the mutated object: JSONPath requires the test-harness feature",
+                path
+            ));
+        }
+
+        failures
+    }
+
+    fn expects_mutation(&self) -> bool {
+        self.mutated_object.is_some() || !self.mutated_object_paths.is_empty()
+    }
+}
+
+/// The answer given by the host to a call, see [`HostCallFixtures`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum HostCallResponse {
+    /// The call succeeds, the policy receives this JSON document
+    Response(serde_json::Value),
+    /// The call fails with this error message
+    Error(String),
+}
+
+/// A recorded host call, with the answer of the host
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HostCallFixture {
+    /// The capability namespace, e.g. `oci` or `kubernetes`
+    pub namespace: String,
+    /// The operation performed, e.g. `v1/manifest_digest`
+    pub operation: String,
+    /// The request sent by the policy
+    pub request: serde_json::Value,
+    #[serde(flatten)]
+    pub response: HostCallResponse,
+}
+
+/// Answers for the host calls performed by a policy during a [`Testcase`].
+///
+/// A fixture matches a host call when namespace, operation and request are
+/// the same. Requests are compared as JSON documents, hence formatting and
+/// key ordering do not matter.
+///
+/// Fixtures can be loaded from a JSON file holding a list of objects like:
+///
+/// ```json
+/// [
+///   {
+///     "namespace": "oci",
+///     "operation": "v1/manifest_digest",
+///     "request": "busybox:latest",
+///     "response": { "digest": "sha256:983" }
+///   },
+///   {
+///     "namespace": "kubernetes",
+///     "operation": "get_resource",
+///     "request": { "api_version": "v1", "kind": "Namespace", "name": "missing", "namespace": null, "disable_cache": false },
+///     "error": "namespace not found"
+///   }
+/// ]
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct HostCallFixtures {
+    fixtures: Vec<HostCallFixture>,
+}
+
+impl HostCallFixtures {
+    /// Create an empty set of fixtures
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the fixtures stored inside of a JSON file
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .map_err(|e| anyhow!("cannot open host call fixtures file {}: {}", path, e))?;
+        serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| anyhow!("cannot parse host call fixtures file {}: {}", path, e))
+    }
+
+    /// Answer the host call with the JSON representation of `response`
+    pub fn with_response<Req, Res>(
+        self,
+        namespace: &str,
+        operation: &str,
+        request: &Req,
+        response: &Res,
+    ) -> anyhow::Result<Self>
+    where
+        Req: Serialize,
+        Res: Serialize,
+    {
+        let response = HostCallResponse::Response(serde_json::to_value(response)?);
+        self.with_fixture(namespace, operation, request, response)
+    }
+
+    /// Answer the host call with the JSON document stored inside of `response_file`
+    pub fn with_response_file<Req: Serialize>(
+        self,
+        namespace: &str,
+        operation: &str,
+        request: &Req,
+        response_file: &str,
+    ) -> anyhow::Result<Self> {
+        let response =
+            HostCallResponse::Response(read_request_file(response_file).map_err(|e| {
+                anyhow!(
+                    "cannot read host call response file {}: {}",
+                    response_file,
+                    e
+                )
+            })?);
+        self.with_fixture(namespace, operation, request, response)
+    }
+
+    /// Make the host call fail with `message`
+    pub fn with_error<Req: Serialize>(
+        self,
+        namespace: &str,
+        operation: &str,
+        request: &Req,
+        message: &str,
+    ) -> anyhow::Result<Self> {
+        let response = HostCallResponse::Error(message.to_string());
+        self.with_fixture(namespace, operation, request, response)
+    }
+
+    fn with_fixture<Req: Serialize>(
+        mut self,
+        namespace: &str,
+        operation: &str,
+        request: &Req,
+        response: HostCallResponse,
+    ) -> anyhow::Result<Self> {
+        self.fixtures.push(HostCallFixture {
+            namespace: namespace.to_string(),
+            operation: operation.to_string(),
+            request: serde_json::to_value(request)?,
+            response,
+        });
+        Ok(self)
+    }
+
+    /// Find the fixture answering the given host call
+    pub fn find(
+        &self,
+        namespace: &str,
+        operation: &str,
+        payload: &[u8],
+    ) -> Option<&HostCallFixture> {
+        let request: serde_json::Value = serde_json::from_slice(payload).ok()?;
+        self.fixtures
+            .iter()
+            .find(|f| f.namespace == namespace && f.operation == operation && f.request == request)
+    }
+}
+
+/// Transport answering host calls with fixtures, keeping track of the calls
+/// that could not be answered
+struct FixtureTransport {
+    fixtures: HostCallFixtures,
+    unanswered: RefCell<Vec<HostCall>>,
+}
+
+impl HostTransport for FixtureTransport {
+    fn host_call(
+        &self,
+        binding: &str,
+        namespace: &str,
+        operation: &str,
+        payload: &[u8],
+    ) -> wapc_guest::CallResult {
+        match self.fixtures.find(namespace, operation, payload) {
+            Some(fixture) => match &fixture.response {
+                HostCallResponse::Response(response) => Ok(serde_json::to_vec(response)?),
+                HostCallResponse::Error(message) => Err(anyhow!("{}", message).into()),
+            },
+            None => {
+                self.unanswered.borrow_mut().push(HostCall {
+                    binding: binding.to_string(),
+                    namespace: namespace.to_string(),
+                    operation: operation.to_string(),
+                    payload: payload.to_vec(),
+                });
+                Err(anyhow!("no fixture for host call {}/{}", namespace, operation).into())
+            }
+        }
+    }
+}
+
+fn default_settings() -> serde_json::Value {
+    json!({})
+}
+
+/// A test case of a [`TestSuite`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TestSuiteCase {
+    pub name: String,
+    /// File holding the admission request, relative to the test suite file.
+    /// Either this or `request` must be set.
+    #[serde(default)]
+    pub fixture_file: Option<String>,
+    /// The admission request, written inline
+    #[serde(default)]
+    pub request: Option<serde_json::Value>,
+    /// Settings of the policy, the ones of the suite are used when not set
+    #[serde(default)]
+    pub settings: Option<serde_json::Value>,
+    /// Whether the request must be accepted
+    pub accepted: bool,
+    /// Expectations about the response, including the mutated object
+    #[serde(default)]
+    pub expect: Expectations,
+    /// Answers to the host calls performed by the policy
+    #[serde(default)]
+    pub host_calls: Option<HostCallFixtures>,
+}
+
+/// A list of test cases loaded from a YAML or JSON file, like:
+///
+/// ```yaml
+/// settings:
+///   allowedRegistries: ["registry.local"]
+/// cases:
+///   - name: reject images coming from docker hub
+///     fixtureFile: fixtures/pod_docker_hub.json
+///     accepted: false
+///     expect:
+///       message:
+///         regex: "^registry docker.io is not allowed"
+///   - name: pin image tags
+///     request:
+///       operation: CREATE
+///       object: { ... }
+///     settings:
+///       allowedRegistries: []
+///       pinTags: true
+///     accepted: true
+///     expect:
+///       mutatedObjectPaths:
+///         "$.spec.containers[*].image": ["registry.local/nginx:1.27"]
+/// ```
+///
+/// Paths of fixture files are relative to the test suite file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TestSuite {
+    /// Settings used by the test cases that do not provide their own ones
+    #[serde(default = "default_settings")]
+    pub settings: serde_json::Value,
+    pub cases: Vec<TestSuiteCase>,
+    /// Directory used to resolve the fixture files
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+impl TestSuite {
+    /// Load a test suite from a YAML or JSON file
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        let file =
+            File::open(path).map_err(|e| anyhow!("cannot open test suite {}: {}", path, e))?;
+        let mut suite: TestSuite = serde_yaml::from_reader(BufReader::new(file))
+            .map_err(|e| anyhow!("cannot parse test suite {}: {}", path, e))?;
+        suite.base_dir = Path::new(path)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Ok(suite)
+    }
+
+    /// Evaluate all the test cases, the failures of all of them are reported
+    /// at once
+    pub fn run(&self, validate: ValidateFn) -> Result<(), TestSuiteFailure> {
+        let failures: Vec<TestFailure> = self
+            .cases
+            .iter()
+            .filter_map(|case| self.run_case(case, validate).err())
+            .collect();
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(TestSuiteFailure {
+                total: self.cases.len(),
+                failures,
+            })
+        }
+    }
+
+    fn run_case(
+        &self,
+        case: &TestSuiteCase,
+        validate: ValidateFn,
+    ) -> Result<ValidationResponse, TestFailure> {
+        let request = match (&case.fixture_file, &case.request) {
+            (Some(fixture_file), None) => {
+                let path = self.base_dir.join(fixture_file);
+                read_request_file(&path.to_string_lossy()).map_err(|e| {
+                    TestFailure::new(
+                        &case.name,
+                        vec![format!(
+                            "cannot read fixture file {}: {}",
+                            path.display(),
+                            e
+                        )],
+                    )
+                })?
+            }
+            (None, Some(request)) => request.clone(),
+            _ => {
+                return Err(TestFailure::new(
+                    &case.name,
+                    vec!["exactly one of fixtureFile and request must be set".to_string()],
+                ))
+            }
+        };
+        let payload = json!({
+            "settings": case.settings.as_ref().unwrap_or(&self.settings),
+            "request": request
+        });
+
+        run_payload(
+            &case.name,
+            validate,
+            &payload.to_string(),
+            case.accepted,
+            &case.expect,
+            case.host_calls.as_ref(),
+        )
+    }
+}
+
+/// Returned when some test cases of a [`TestSuite`] fail
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestSuiteFailure {
+    /// Number of test cases of the suite
+    pub total: usize,
+    pub failures: Vec<TestFailure>,
+}
+
+impl fmt::Display for TestSuiteFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} test cases failed",
+            self.failures.len(),
+            self.total
+        )?;
+        for failure in &self.failures {
+            write!(f, "\n  - {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TestSuiteFailure {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::oci;
+    use crate::{accept_request, reject_request};
+
+    #[derive(Serialize, Deserialize, Default)]
+    struct Settings {}
+
+    // Accept the request only when the digest of busybox can be computed
+    fn validate(_payload: &[u8]) -> wapc_guest::CallResult {
+        match oci::get_manifest_digest("busybox:latest") {
+            Ok(response) if response.digest == "sha256:983" => accept_request(),
+            Ok(response) => reject_request(Some(response.digest), None, None, None),
+            Err(e) => reject_request(Some(e.to_string()), None, None, None),
+        }
+    }
+
+    fn testcase(expected_validation_result: bool) -> Testcase<Settings> {
+        Testcase {
+            name: "busybox digest".to_string(),
+            fixture_file: "test_data/pod_creation.json".to_string(),
+            expected_validation_result,
+            settings: Settings {},
+        }
+    }
+
+    #[test]
+    fn host_calls_answered_by_fixtures() {
+        let fixtures = HostCallFixtures::new()
+            .with_response(
+                "oci",
+                "v1/manifest_digest",
+                &"busybox:latest",
+                &json!({"digest": "sha256:983"}),
+            )
+            .unwrap();
+
+        assert!(testcase(true)
+            .eval_with_host_calls(validate, &fixtures)
+            .is_ok());
+    }
+
+    #[test]
+    fn host_call_fixtures_loaded_from_file() {
+        let fixtures = HostCallFixtures::from_file("test_data/host_call_fixtures.json").unwrap();
+
+        assert!(testcase(false)
+            .eval_with_host_calls(validate, &fixtures)
+            .is_ok());
+        assert!(fixtures
+            .find(
+                "kubernetes",
+                "get_resource",
+                br#"{"name":"missing","namespace":null,"kind":"Namespace","api_version":"v1","disable_cache":false}"#
+            )
+            .is_some());
+    }
+
+    #[test]
+    fn unanswered_host_calls_are_reported() {
+        let fixtures = HostCallFixtures::new()
+            .with_response(
+                "oci",
+                "v1/manifest_digest",
+                &"nginx:latest",
+                &json!({"digest": "sha256:983"}),
+            )
+            .unwrap();
+
+        let err = testcase(false)
+            .check_with_host_calls(validate, &Expectations::default(), &fixtures)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "test case 'busybox digest' failed: host calls without a fixture: oci/v1/manifest_digest \"busybox:latest\""
+        );
+    }
+
+    fn mutate(_payload: &[u8]) -> wapc_guest::CallResult {
+        crate::response::ValidationResponseBuilder::mutate(json!({
+            "metadata": {
+                "labels": {"app": "nginx", "owner": "team-a"}
+            },
+            "spec": {
+                "containers": [{"image": "nginx:1.27"}, {"image": "busybox:1.37"}]
+            }
+        }))
+        .warning("image tags have been pinned")
+        .build()
+    }
+
+    fn mutation_testcase() -> Testcase<Settings> {
+        Testcase {
+            name: "pin images".to_string(),
+            fixture_file: "test_data/pod_creation.json".to_string(),
+            expected_validation_result: true,
+            settings: Settings {},
+        }
+    }
+
+    #[cfg(feature = "test-harness")]
+    #[test]
+    fn expectations_met() {
+        let expectations = Expectations {
+            warnings: Some(vec!["image tags have been pinned".to_string()]),
+            mutated_object_paths: BTreeMap::from([
+                ("$.metadata.labels.owner".to_string(), json!("team-a")),
+                (
+                    "$.spec.containers[*].image".to_string(),
+                    json!(["nginx:1.27", "busybox:1.37"]),
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        assert!(mutation_testcase().check(mutate, &expectations).is_ok());
+    }
+
+    #[cfg(feature = "test-harness")]
+    #[test]
+    fn all_unmet_expectations_are_reported() {
+        let expectations = Expectations {
+            message: Some(MessageExpectation::Regex("^pinned .*".to_string())),
+            code: Some(400),
+            mutated_object: Some(json!({"metadata": {}})),
+            mutated_object_paths: BTreeMap::from([(
+                "$.metadata.labels.owner".to_string(),
+                json!("team-b"),
+            )]),
+            ..Default::default()
+        };
+
+        let failure = mutation_testcase()
+            .check(mutate, &expectations)
+            .unwrap_err();
+        assert_eq!(failure.name, "pin images");
+        assert_eq!(failure.failures.len(), 4, "{}", failure);
+        assert_eq!(
+            failure.failures[3],
+            r#"got "team-a" at $.metadata.labels.owner of the mutated object instead of "team-b""#
+        );
+    }
+
+    #[test]
+    fn missing_fixture_file_is_reported() {
+        let mut testcase = mutation_testcase();
+        testcase.fixture_file = "test_data/missing.json".to_string();
+
+        let err = testcase
+            .check(mutate, &Expectations::default())
+            .unwrap_err();
+        assert!(err.to_string().starts_with(
+            "test case 'pin images' failed: cannot read fixture file test_data/missing.json"
+        ));
+    }
+
+    #[cfg(not(feature = "test-harness"))]
+    #[test]
+    fn expectations_requiring_the_test_harness_feature_are_reported() {
+        let expectations = Expectations {
+            message: Some(MessageExpectation::Regex("^pinned .*".to_string())),
+            mutated_object_paths: BTreeMap::from([(
+                "$.metadata.labels.owner".to_string(),
+                json!("team-a"),
+            )]),
+            ..Default::default()
+        };
+
+        let failure = mutation_testcase()
+            .check(mutate, &expectations)
+            .unwrap_err();
+        assert_eq!(
+            failure.failures,
+            vec![
+                r#"cannot match message against "^pinned .*": regex requires the test-harness feature"#,
+                "cannot check $.metadata.labels.owner of the mutated object: JSONPath requires the test-harness feature",
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Failure for test case: 'pin images': got true instead of false")]
+    fn eval_panics_on_unexpected_outcome() {
+        let mut testcase = mutation_testcase();
+        testcase.expected_validation_result = false;
+
+        let _ = testcase.eval(mutate);
+    }
+
+    #[test]
+    fn eval_reports_evaluation_errors() {
+        let mut missing_fixture = mutation_testcase();
+        missing_fixture.fixture_file = "test_data/missing.json".to_string();
+        assert!(missing_fixture.eval(mutate).is_err());
+
+        let fixtures = HostCallFixtures::new();
+        let err = testcase(true)
+            .eval_with_host_calls(validate, &fixtures)
+            .unwrap_err();
+        assert!(err.to_string().contains("host calls without a fixture"));
+    }
+
+    #[test]
+    fn unexpected_mutated_object_is_reported() {
+        let expectations = Expectations {
+            mutated_object: Some(json!({"metadata": {}})),
+            ..Default::default()
+        };
+
+        let failure = mutation_testcase()
+            .check(mutate, &expectations)
+            .unwrap_err();
+        assert_eq!(failure.failures.len(), 1, "{}", failure);
+        assert!(failure.failures[0].starts_with("got mutated object "));
+    }
+
+    #[test]
+    fn expectations_from_yaml() {
+        let expectations: Expectations = serde_yaml::from_str(
+            r#"
+message:
+  exact: "image not allowed"
+code: 403
+auditAnnotations:
+  reason: denied
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            expectations.message,
+            Some(MessageExpectation::Exact("image not allowed".to_string()))
+        );
+        assert_eq!(expectations.code, Some(403));
+        assert!(expectations.mutated_object_paths.is_empty());
+    }
+
+    // Reject nginx pods
+    fn reject_nginx(payload: &[u8]) -> wapc_guest::CallResult {
+        let payload: serde_json::Value = serde_json::from_slice(payload)?;
+        if payload["request"]["object"]["metadata"]["labels"]["app"] == "nginx" {
+            return reject_request(
+                Some("nginx is not allowed".to_string()),
+                Some(403),
+                None,
+                None,
+            );
+        }
+        accept_request()
+    }
+
+    #[test]
+    fn run_test_suite() {
+        let suite = TestSuite::from_file("test_data/test_suite.yaml").unwrap();
+        assert_eq!(suite.cases.len(), 2);
+        assert!(suite.run(reject_nginx).is_ok());
+    }
+
+    #[test]
+    fn test_suite_reports_all_failures() {
+        let mut suite = TestSuite::from_file("test_data/test_suite.yaml").unwrap();
+        for case in suite.cases.iter_mut() {
+            case.accepted = !case.accepted;
+        }
+
+        let failure = suite.run(reject_nginx).unwrap_err();
+        assert_eq!(failure.total, 2);
+        assert_eq!(
+            failure
+                .failures
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["reject nginx", "accept busybox"]
+        );
     }
 }
@@ -0,0 +1,392 @@
+use anyhow::anyhow;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{DeleteOptions, ObjectMeta};
+use k8s_openapi::{Metadata, Resource};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+
+use crate::request::{
+    CreateOptions, GroupVersionKind, GroupVersionResource, KubernetesAdmissionRequest, Operation,
+    UpdateOptions,
+};
+
+/// Build the admission requests used by tests starting from typed
+/// k8s-openapi objects. The kind, the resource, the name and the namespace
+/// of the request are taken from the object.
+///
+/// # Example
+///
+/// ```
+/// use k8s_openapi::api::core::v1::Pod;
+/// use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+/// use kubewarden_policy_sdk::test::AdmissionRequestBuilder;
+///
+/// let pod = Pod {
+///     metadata: ObjectMeta {
+///         name: Some("nginx".to_string()),
+///         ..Default::default()
+///     },
+///     ..Default::default()
+/// };
+///
+/// let request = AdmissionRequestBuilder::create(&pod)
+///     .user("alice")
+///     .groups(["developers", "system:authenticated"])
+///     .namespace("team-a")
+///     .dry_run()
+///     .build()
+///     .unwrap();
+/// assert_eq!(request.name, "nginx");
+/// assert_eq!(request.resource.resource, "pods");
+/// ```
+#[derive(Debug, Clone)]
+pub struct AdmissionRequestBuilder {
+    request: KubernetesAdmissionRequest,
+    errors: Vec<String>,
+}
+
+impl AdmissionRequestBuilder {
+    /// A request creating `object`
+    pub fn create<K>(object: &K) -> Self
+    where
+        K: Resource + Metadata<Ty = ObjectMeta> + Serialize,
+    {
+        Self::new::<K>(Operation::Create, object.metadata()).with_object(object)
+    }
+
+    /// A request updating `old_object` into `object`
+    pub fn update<K>(old_object: &K, object: &K) -> Self
+    where
+        K: Resource + Metadata<Ty = ObjectMeta> + Serialize,
+    {
+        Self::new::<K>(Operation::Update, object.metadata())
+            .with_object(object)
+            .with_old_object(old_object)
+    }
+
+    /// A request deleting `old_object`
+    pub fn delete<K>(old_object: &K) -> Self
+    where
+        K: Resource + Metadata<Ty = ObjectMeta> + Serialize,
+    {
+        Self::new::<K>(Operation::Delete, old_object.metadata()).with_old_object(old_object)
+    }
+
+    fn new<K: Resource>(operation: Operation, metadata: &ObjectMeta) -> Self {
+        let kind = GroupVersionKind::of::<K>();
+        let resource = GroupVersionResource::of::<K>();
+        AdmissionRequestBuilder {
+            request: KubernetesAdmissionRequest {
+                request_kind: kind.clone(),
+                kind,
+                request_resource: resource.clone(),
+                resource,
+                name: metadata.name.clone().unwrap_or_default(),
+                namespace: metadata.namespace.clone().unwrap_or_default(),
+                operation,
+                ..Default::default()
+            },
+            errors: Vec::new(),
+        }
+    }
+
+    fn with_object<K: Serialize>(mut self, object: &K) -> Self {
+        match serde_json::to_value(object) {
+            Ok(object) => self.request.object = object,
+            Err(e) => self.errors.push(format!("cannot serialize object: {}", e)),
+        }
+        self
+    }
+
+    fn with_old_object<K: Serialize>(mut self, old_object: &K) -> Self {
+        match serde_json::to_value(old_object) {
+            Ok(old_object) => self.request.old_object = old_object,
+            Err(e) => self
+                .errors
+                .push(format!("cannot serialize old object: {}", e)),
+        }
+        self
+    }
+
+    /// Set the name of the user performing the request
+    pub fn user(mut self, username: &str) -> Self {
+        self.request.user_info.username = username.to_string();
+        self
+    }
+
+    /// Set the groups the user performing the request belongs to
+    pub fn groups<I, S>(mut self, groups: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.request.user_info.groups = groups.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the namespace of the request, this is also set as the namespace
+    /// of the object
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.request.namespace = namespace.to_string();
+        for object in [&mut self.request.object, &mut self.request.old_object] {
+            if let Some(metadata) = object.get_mut("metadata").and_then(|m| m.as_object_mut()) {
+                metadata.insert("namespace".to_string(), json!(namespace));
+            }
+        }
+        self
+    }
+
+    /// Set the subresource being requested (e.g. `status` or `scale`)
+    pub fn sub_resource(mut self, sub_resource: &str) -> Self {
+        self.request.sub_resource = sub_resource.to_string();
+        self.request.request_sub_resource = sub_resource.to_string();
+        self
+    }
+
+    /// Mark the request as a dry run, its changes are not persisted
+    pub fn dry_run(mut self) -> Self {
+        self.request.dry_run = true;
+        self
+    }
+
+    /// Set the UID of the request. When not set, the UID is derived from
+    /// the content of the request.
+    pub fn uid(mut self, uid: &str) -> Self {
+        self.request.uid = uid.to_string();
+        self
+    }
+
+    /// Build the admission request
+    pub fn build(self) -> anyhow::Result<KubernetesAdmissionRequest> {
+        if !self.errors.is_empty() {
+            return Err(anyhow!(
+                "cannot build admission request: {}",
+                self.errors.join(", ")
+            ));
+        }
+
+        let mut request = self.request;
+        let dry_run = request.dry_run.then(|| vec!["All".to_string()]);
+        let options = match request.operation {
+            Operation::Create => serde_json::to_value(CreateOptions {
+                api_version: Some("meta.k8s.io/v1".to_string()),
+                kind: Some("CreateOptions".to_string()),
+                dry_run,
+                ..Default::default()
+            })?,
+            Operation::Update => serde_json::to_value(UpdateOptions {
+                api_version: Some("meta.k8s.io/v1".to_string()),
+                kind: Some("UpdateOptions".to_string()),
+                dry_run,
+                ..Default::default()
+            })?,
+            _ => serde_json::to_value(DeleteOptions {
+                api_version: Some("meta.k8s.io/v1".to_string()),
+                kind: Some("DeleteOptions".to_string()),
+                dry_run,
+                ..Default::default()
+            })?,
+        };
+        request.options = match options {
+            serde_json::Value::Object(options) => options
+                .into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .collect::<HashMap<String, serde_json::Value>>(),
+            _ => HashMap::new(),
+        };
+
+        if request.uid.is_empty() {
+            request.uid = derive_uid(&request);
+        }
+        Ok(request)
+    }
+
+    /// Build the payload given to the `validate` function of a policy
+    pub fn payload<T: Serialize>(self, settings: &T) -> anyhow::Result<Vec<u8>> {
+        let request = self.build()?;
+        Ok(serde_json::to_vec(&json!({
+            "settings": settings,
+            "request": request,
+        }))?)
+    }
+}
+
+/// Derive a UID, formatted like the ones assigned by the API server, from
+/// the content of the request. The same request always gets the same UID,
+/// regardless of the Rust version used to build the tests.
+fn derive_uid(request: &KubernetesAdmissionRequest) -> String {
+    let hash = [
+        json!(request.operation.as_str()),
+        json!(request.kind.to_string()),
+        json!(request.namespace),
+        json!(request.name),
+        request.object.clone(),
+        request.old_object.clone(),
+    ]
+    .iter()
+    .fold(FNV_OFFSET_BASIS, fnv1a_value);
+
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        hash >> 32,
+        (hash >> 16) & 0xffff,
+        hash & 0xffff,
+        (hash >> 48) & 0xffff,
+        hash & 0xffff_ffff_ffff
+    )
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Feed `bytes` to the 64 bit FNV-1a hash function, starting from `hash`.
+/// Unlike `DefaultHasher`, the algorithm is fixed, hence its output is
+/// stable across Rust releases.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Feed `value` to the FNV-1a hash function. The keys of the objects are
+/// sorted, the result doesn't depend on the `preserve_order` feature of
+/// `serde_json`
+fn fnv1a_value(hash: u64, value: &serde_json::Value) -> u64 {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            let hash = fnv1a(hash, b"{");
+            let hash = entries.into_iter().fold(hash, |hash, (key, value)| {
+                let hash = fnv1a_value(hash, &json!(key));
+                fnv1a_value(fnv1a(hash, b":"), value)
+            });
+            fnv1a(hash, b"}")
+        }
+        serde_json::Value::Array(items) => {
+            let hash = items.iter().fold(fnv1a(hash, b"["), fnv1a_value);
+            fnv1a(hash, b"]")
+        }
+        // the values are separated, otherwise moving bytes from one value to
+        // the next one would not change the hash
+        scalar => fnv1a(fnv1a(hash, scalar.to_string().as_bytes()), &[0]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::{OperationOptions, ValidationRequest};
+    use k8s_openapi::api::apps::v1::Deployment;
+    use k8s_openapi::api::core::v1::Pod;
+
+    fn pod(name: &str) -> Pod {
+        Pod {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                namespace: Some("default".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn create_request() {
+        let request = AdmissionRequestBuilder::create(&pod("nginx"))
+            .user("alice")
+            .groups(["developers"])
+            .namespace("team-a")
+            .dry_run()
+            .build()
+            .unwrap();
+
+        assert!(request.kind.is::<Pod>());
+        assert!(request.request_kind.is::<Pod>());
+        assert!(request.resource.is::<Pod>());
+        assert!(request.request_resource.is::<Pod>());
+        assert_eq!(request.operation, Operation::Create);
+        assert_eq!(request.name, "nginx");
+        assert_eq!(request.namespace, "team-a");
+        assert_eq!(request.object["metadata"]["namespace"], "team-a");
+        assert_eq!(request.object["kind"], "Pod");
+        assert!(request.old_object.is_null());
+        assert_eq!(request.user_info.username, "alice");
+        assert!(request.user_info.groups.contains("developers"));
+        assert_eq!(request.uid.len(), 36);
+        match request.operation_options().unwrap() {
+            Some(OperationOptions::Create(options)) => {
+                assert_eq!(options.dry_run, Some(vec!["All".to_string()]))
+            }
+            other => panic!("unexpected options {:?}", other),
+        }
+    }
+
+    #[test]
+    fn update_and_delete_requests() {
+        let update = AdmissionRequestBuilder::update(&pod("nginx"), &pod("nginx"))
+            .build()
+            .unwrap();
+        assert_eq!(update.operation, Operation::Update);
+        assert_eq!(update.object, update.old_object);
+
+        let delete = AdmissionRequestBuilder::delete(&Deployment::default())
+            .uid("b31b3d9e-3c8f-4d5b-9a8e-2f4b1c1d6f0a")
+            .build()
+            .unwrap();
+        assert_eq!(delete.operation, Operation::Delete);
+        assert!(delete.object.is_null());
+        assert_eq!(delete.resource.to_string(), "apps/v1/deployments");
+        assert_eq!(delete.uid, "b31b3d9e-3c8f-4d5b-9a8e-2f4b1c1d6f0a");
+        assert!(matches!(
+            delete.operation_options().unwrap(),
+            Some(OperationOptions::Delete(_))
+        ));
+    }
+
+    #[test]
+    fn uid_is_stable() {
+        let first = AdmissionRequestBuilder::create(&pod("nginx"))
+            .build()
+            .unwrap();
+        let second = AdmissionRequestBuilder::create(&pod("nginx"))
+            .build()
+            .unwrap();
+        let other = AdmissionRequestBuilder::create(&pod("busybox"))
+            .build()
+            .unwrap();
+
+        assert_eq!(first.uid, second.uid);
+        assert_ne!(first.uid, other.uid);
+        // the UID must not change across Rust releases
+        assert_eq!(first.uid, "066bba6d-9546-3fb8-066b-ba6d95463fb8");
+    }
+
+    #[test]
+    fn fnv1a_known_values() {
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"foobar"), 0x8594_4171_f739_67e8);
+
+        let hash = |value| fnv1a_value(FNV_OFFSET_BASIS, &value);
+        assert_eq!(
+            hash(json!({"a": 1, "b": [true, null]})),
+            hash(json!({"b": [true, null], "a": 1}))
+        );
+        assert_ne!(hash(json!(["ab", "c"])), hash(json!(["a", "bc"])));
+    }
+
+    #[test]
+    fn payload_is_a_validation_request() {
+        let payload = AdmissionRequestBuilder::create(&pod("nginx"))
+            .payload(&())
+            .unwrap();
+        let validation_request = ValidationRequest::<()>::new(&payload).unwrap();
+
+        assert_eq!(validation_request.request.name, "nginx");
+        assert_eq!(
+            validation_request.object_as::<Pod>().unwrap().metadata.name,
+            Some("nginx".to_string())
+        );
+    }
+}